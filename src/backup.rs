@@ -0,0 +1,3 @@
+pub(crate) mod atomic_write;
+pub(crate) mod file_descriptor;
+pub(crate) mod terminal_backup;
@@ -0,0 +1,77 @@
+use crate::backup::file_descriptor::FileDesc;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    os::unix::fs::{MetadataExt, OpenOptionsExt},
+    path::{Path, PathBuf},
+    process,
+};
+
+/// write_atomically replaces the file at `path` with `contents` so that a crash or a full disk
+/// leaves either the old or the new file behind, never a truncated one.
+/// The contents go to a temporary file in the same directory first, which is synced and then
+/// renamed over the original. Mode bits and ownership of an existing file are carried over.
+pub(crate) fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    write_atomically_like(path, contents, path)
+}
+
+/// write_atomically_like works like write_atomically, but takes mode bits and ownership from
+/// `template` instead, e.g. so that a swap file is exactly as private as the file it backs up.
+pub(crate) fn write_atomically_like(
+    path: &Path,
+    contents: &[u8],
+    template: &Path,
+) -> io::Result<()> {
+    // Write through symlinks instead of replacing the link itself
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let Some(file_name) = path.file_name() else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a file", path.display()),
+        ));
+    };
+    let temp_path = directory.join(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        process::id()
+    ));
+
+    let result =
+        write_temp_file(&temp_path, template, contents).and_then(|_| fs::rename(&temp_path, &path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result?;
+
+    // Sync the directory as well so that the rename itself survives a crash
+    if let Ok(directory) = File::open(&directory) {
+        let _ = FileDesc::borrow(&directory).sync();
+    }
+    Ok(())
+}
+
+fn write_temp_file(temp_path: &Path, template: &Path, contents: &[u8]) -> io::Result<()> {
+    let original = fs::metadata(template).ok();
+    // Until it has the mode of the original, only the owner may read the temporary file.
+    // A new file gets the usual mode, restricted by the umask.
+    let file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(if original.is_some() { 0o600 } else { 0o666 })
+        .open(temp_path)?;
+
+    let fd = FileDesc::borrow(&file);
+    if let Some(metadata) = original {
+        // Only privileged users may give a file away, so a failure here is not fatal
+        let _ = fd.chown(metadata.uid(), metadata.gid());
+        // Changing the owner may clear the setuid and setgid bits, so the mode comes last
+        file.set_permissions(metadata.permissions())?;
+    }
+    // Writing through a shared reference keeps the borrowed descriptor usable
+    (&file).write_all(contents)?;
+    fd.sync()
+}
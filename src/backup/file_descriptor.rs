@@ -1,4 +1,9 @@
-use std::{fs, io, os::fd::{IntoRawFd, RawFd}};
+use crate::backup::terminal_backup::wrap_with_result;
+use std::{
+    fs::{self, File},
+    io,
+    os::fd::{AsRawFd, IntoRawFd, RawFd},
+};
 
 #[derive(Debug)]
 pub(crate) struct FileDesc<'a> {
@@ -6,12 +11,31 @@ pub(crate) struct FileDesc<'a> {
     _phantom: std::marker::PhantomData<&'a ()>,
 }
 
+impl<'a> FileDesc<'a> {
+    /// borrow wraps the descriptor of an open file without taking ownership of it.
+    pub(crate) fn borrow(file: &'a File) -> Self {
+        FileDesc {
+            fd: file.as_raw_fd(),
+            _phantom: std::marker::PhantomData,
+        }
+    }
+}
+
 impl FileDesc<'_> {
     pub(crate) fn raw_fd(&self) -> RawFd {
         self.fd
     }
+
+    pub(crate) fn sync(&self) -> io::Result<()> {
+        wrap_with_result(unsafe { libc::fsync(self.raw_fd()) })
+    }
+
+    pub(crate) fn chown(&self, uid: u32, gid: u32) -> io::Result<()> {
+        wrap_with_result(unsafe { libc::fchown(self.raw_fd(), uid, gid) })
+    }
 }
 
+#[allow(dead_code)]
 pub(crate) fn create_tty_fd() -> io::Result<FileDesc<'static>> {
     let fd = if unsafe { libc::isatty(libc::STDIN_FILENO) == 1 } {
         libc::STDIN_FILENO
@@ -2,6 +2,7 @@ use std::{io, mem, os::fd::RawFd};
 
 use libc::{tcgetattr, termios};
 
+#[allow(dead_code)]
 pub(crate) fn get_terminal_attr(fd: RawFd) -> io::Result<termios> {
     unsafe {
         let mut tios = mem::zeroed();
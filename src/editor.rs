@@ -1,5 +1,9 @@
 pub(crate) mod buffer;
 pub(crate) mod editor_command;
+pub(crate) mod file_type;
+pub(crate) mod history;
+pub(crate) mod indentation;
+pub(crate) mod line_ending;
 
 use crate::{editor::editor_command::EditorCommand, view::View};
 use std::{
@@ -33,9 +37,10 @@ where
         let position = self.ui.get_position()?;
         self.ui.hide_caret()?;
         self.ui.render()?;
-        eprintln!("DEBUG: Refreshed screen at position: ({}, {})", position.x, position.y);
-        self.ui.move_caret_to_position(position)?;
-        self.ui.show_caret()?;
+        if let Some(position) = position {
+            self.ui.move_caret_to_position(position)?;
+            self.ui.show_caret()?;
+        }
         self.ui.flush()?;
         Ok(())
     }
@@ -46,11 +51,18 @@ where
             if self.should_quit {
                 break;
             }
-            self.ui.evaluate_keypress(|command| match command {
-                EditorCommand::Quit => {
+            let mut editor_command = None;
+            self.ui
+                .evaluate_keypress(|command| editor_command = Some(command))?;
+            match editor_command {
+                Some(EditorCommand::Quit) => {
                     self.should_quit = true;
                 }
-            })?;
+                Some(EditorCommand::Save) => {
+                    self.ui.save();
+                }
+                None => {}
+            }
         }
         self.ui.terminate()?;
         Ok(())
@@ -65,5 +77,10 @@ impl<U: View> Drop for Editor<U> {
             return;
         }
         eprintln!("Editor crashed unexpectedly. Terminal state restored.");
+        if let Ok(swap_paths) = self.ui.write_swap() {
+            for swap_path in swap_paths {
+                eprintln!("Unsaved changes were written to {}.", swap_path.display());
+            }
+        }
     }
 }
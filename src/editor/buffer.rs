@@ -1,69 +1,847 @@
-use crate::view::{Line, Location};
+use crate::{
+    backup::{
+        atomic_write::{write_atomically, write_atomically_like},
+        terminal_backup::wrap_with_result,
+    },
+    editor::{
+        file_type::FileType,
+        history::{Edit, History},
+        indentation::Indentation,
+        line_ending::LineEnding,
+    },
+    view::{Line, Location},
+};
+use regex::Regex;
+use ropey::Rope;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+};
+use unicode_segmentation::UnicodeSegmentation;
 
+/// Buffer keeps the document in a rope, in which every line is terminated by '\n'.
+/// Lines are only segmented into graphemes when they are asked for, so edits stay cheap
+/// even for very large files.
 #[derive(Clone)]
 pub struct Buffer {
-    pub lines: Vec<Line>,
+    text: Rope,
+    pub file_path: Option<PathBuf>,
+    pub is_modified: bool,
+    pub line_ending: LineEnding,
+    pub has_trailing_newline: bool,
+    pub has_bom: bool,
+    pub history: History,
+    pub indentation: Indentation,
+    // Counts the changes to the text, however they were made
+    revision: usize,
+}
+
+const BOM: char = '\u{feff}';
+/// Start of the first line of a swap file, which names the process that writes it
+const SWAP_HEADER: &str = "hecto swap file of process ";
+
+/// SwapState tells what a swap file next to the original means for this session.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SwapState {
+    None,
+    // Left behind by a session that ended without saving
+    Recoverable { is_older_than_file: bool },
+    // Still written by another running session, which must not lose it
+    InUse { pid: u32 },
+}
+
+/// RegexMatch is a match of a regular expression within a single line, together with the
+/// replacement template expanded from its capture groups.
+#[derive(Clone, Debug)]
+pub struct RegexMatch {
+    pub start: Location,
+    pub end: Location,
+    pub replacement: String,
 }
 
 impl Buffer {
+    /// new normalises the content to '\n' line breaks while remembering the line ending style,
+    /// the byte order mark and the final newline, so that `contents` can reproduce the text
+    /// exactly. A file which mixes CRLF and LF is not normalised, so that saving it does not
+    /// change the ending of any line.
     pub fn new(content: String) -> Self {
-        let lines = content.lines().into_iter().map(|s| s.into()).collect();
-        Self { lines }
+        let has_bom = content.starts_with(BOM);
+        let text = content.strip_prefix(BOM).unwrap_or(&content);
+        let line_ending = LineEnding::detect(text);
+        // New files get a trailing newline, like most tools expect
+        let has_trailing_newline = text.is_empty() || text.ends_with('\n');
+
+        let mut text = match line_ending {
+            LineEnding::CrLf => text.replace("\r\n", "\n"),
+            LineEnding::Lf | LineEnding::Mixed => text.to_string(),
+        };
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+
+        Self {
+            text: Rope::from_str(&text),
+            file_path: None,
+            is_modified: false,
+            line_ending,
+            has_trailing_newline,
+            has_bom,
+            history: History::default(),
+            indentation: Indentation::default(),
+            revision: 0,
+        }
+    }
+
+    pub fn load(file_path: &str) -> io::Result<Self> {
+        let content = fs::read_to_string(file_path)?;
+        let mut buffer = Self::new(content);
+        buffer.file_path = Some(PathBuf::from(file_path));
+        Ok(buffer)
+    }
+
+    /// save writes every line back to the file the buffer was loaded from, each line
+    /// terminated by a newline.
+    pub fn save(&mut self) -> io::Result<()> {
+        let Some(file_path) = self.file_path.clone() else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "Buffer has no associated file",
+            ));
+        };
+        self.write_to(&file_path)
+    }
+
+    /// save_as writes the buffer to a new path and binds the buffer to it for future saves.
+    pub fn save_as(&mut self, file_path: &str) -> io::Result<()> {
+        let file_path = PathBuf::from(file_path);
+        self.write_to(&file_path)?;
+        self.file_path = Some(file_path);
+        Ok(())
+    }
+
+    fn write_to(&mut self, file_path: &Path) -> io::Result<()> {
+        write_atomically(file_path, self.contents().as_bytes())?;
+        self.is_modified = false;
+        self.history.mark_saved();
+        // The swap file only holds changes that are now on disk
+        let _ = self.remove_swap();
+        Ok(())
+    }
+
+    /// is_bound_to tells whether saving the buffer writes to the given path, however either
+    /// of them is spelled. Paths that do not exist yet are compared as they are.
+    pub fn is_bound_to(&self, path: &Path) -> bool {
+        let canonical = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        self.file_path
+            .as_deref()
+            .is_some_and(|file_path| canonical(file_path) == canonical(path))
+    }
+
+    /// display_name returns the file path as shown to the user.
+    pub fn display_name(&self) -> String {
+        self.file_path.as_ref().map_or_else(
+            || "[No Name]".to_string(),
+            |path| path.to_string_lossy().to_string(),
+        )
+    }
+
+    pub fn file_type(&self) -> FileType {
+        self.file_path
+            .as_deref()
+            .map(FileType::detect)
+            .unwrap_or_default()
+    }
+
+    /// swap_path returns the hidden file next to the original that holds unsaved changes,
+    /// e.g. `notes.txt` is swapped to `.notes.txt.swp`.
+    pub fn swap_path(&self) -> Option<PathBuf> {
+        let file_path = self.file_path.as_ref()?;
+        let file_name = file_path.file_name()?;
+        Some(file_path.with_file_name(format!(".{}.swp", file_name.to_string_lossy())))
+    }
+
+    /// swap_state checks who wrote the swap file and whether the original has been changed
+    /// since.
+    pub fn swap_state(&self) -> SwapState {
+        let Some(swap_path) = self.swap_path() else {
+            return SwapState::None;
+        };
+        if !swap_path.exists() {
+            return SwapState::None;
+        }
+        if let Some(pid) = live_swap_owner(&swap_path) {
+            return SwapState::InUse { pid };
+        }
+        let modified_at = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified());
+        let is_older_than_file = match (self.file_path.as_deref(), modified_at(&swap_path)) {
+            (Some(file_path), Ok(swap_modified_at)) => {
+                modified_at(file_path).is_ok_and(|modified_at| modified_at > swap_modified_at)
+            }
+            _ => false,
+        };
+        SwapState::Recoverable { is_older_than_file }
+    }
+
+    /// write_swap writes the contents after a header naming this process. A swap file that
+    /// another running session writes is left alone.
+    pub fn write_swap(&self) -> io::Result<Option<PathBuf>> {
+        let Some(swap_path) = self.swap_path() else {
+            return Ok(None);
+        };
+        if live_swap_owner(&swap_path).is_some() {
+            return Ok(None);
+        }
+        let swap = format!("{SWAP_HEADER}{}\n{}", process::id(), self.contents());
+        // The unsaved changes must not be readable by anyone who cannot read the file
+        let file_path = self.file_path.as_deref().unwrap_or(&swap_path);
+        write_atomically_like(&swap_path, swap.as_bytes(), file_path)?;
+        Ok(Some(swap_path))
+    }
+
+    /// remove_swap removes the swap file once its changes are saved or discarded. Only a swap
+    /// file written by this process is removed, so a swap left by a crash survives until the
+    /// user has decided about it.
+    pub fn remove_swap(&self) -> io::Result<()> {
+        match self.swap_path() {
+            Some(swap_path) if swap_owner(&swap_path) == Some(process::id()) => {
+                fs::remove_file(swap_path)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// discard_swap removes a swap file left by a crash after the user declined to recover it.
+    pub fn discard_swap(&self) -> io::Result<()> {
+        match self.swap_path() {
+            Some(swap_path) if swap_path.exists() && live_swap_owner(&swap_path).is_none() => {
+                fs::remove_file(swap_path)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// recover_swap replaces the lines with the content of the swap file.
+    /// The buffer stays bound to the original file, keeps its settings and is marked as
+    /// modified.
+    pub fn recover_swap(&mut self) -> io::Result<()> {
+        let Some(swap_path) = self.swap_path() else {
+            return Ok(());
+        };
+        let swap = fs::read_to_string(swap_path)?;
+        let content = match swap.strip_prefix(SWAP_HEADER) {
+            Some(rest) => rest.split_once('\n').map_or("", |(_, content)| content),
+            None => &swap,
+        };
+        let mut recovered = Self::new(content.to_string());
+        recovered.file_path = self.file_path.take();
+        // Tab settings come from the command line, not from the file
+        recovered.indentation = self.indentation;
+        recovered.is_modified = true;
+        recovered.history = std::mem::take(&mut self.history);
+        // Undo cannot go back to the text on disk from here
+        recovered.history.forget_save_point();
+        recovered.revision = self.revision.wrapping_add(1);
+        *self = recovered;
+        Ok(())
     }
 
-    pub fn line_count(&mut self) -> usize {
-        self.lines.len()
+    /// contents serialises the lines back into the text that is written to disk.
+    pub fn contents(&self) -> String {
+        let mut text = self.text.to_string();
+        if !self.has_trailing_newline {
+            text.pop();
+        }
+        if self.line_ending == LineEnding::CrLf {
+            text = text.replace('\n', LineEnding::CrLf.as_str());
+        }
+        if self.has_bom {
+            text.insert(0, BOM);
+        }
+        text
     }
 
-    pub fn insert_newline(&mut self, at: Location) {
-        if at.line_index == self.lines.len() {
-            self.lines.push(Line::default());
-        } else if let Some(line) = self.lines.get_mut(at.line_index) {
-            let grapheme = line.split(at.grapheme_index);
-            self.lines.insert(at.line_index.saturating_add(1), grapheme);
+    /// revision changes whenever the text changes, so that callers can tell whether it has
+    /// been edited since they last looked.
+    pub fn revision(&self) -> usize {
+        self.revision
+    }
+
+    pub fn line_count(&self) -> usize {
+        // The rope counts the empty line after the final '\n' as well
+        self.text.len_lines().saturating_sub(1)
+    }
+
+    /// line segments the line at the given index into graphemes.
+    pub fn line(&self, line_index: usize) -> Option<Line> {
+        if line_index >= self.line_count() {
+            return None;
+        }
+        Some(Line::with_indentation(
+            self.line_text(line_index).as_str(),
+            self.indentation,
+        ))
+    }
+
+    /// text_between returns the text from one location up to another, with lines separated
+    /// by '\n'.
+    pub fn text_between(&self, from: Location, to: Location) -> String {
+        match (self.char_index(from), self.char_index(to)) {
+            (Some(start), Some(end)) if start < end => self.text.slice(start..end).to_string(),
+            _ => String::new(),
+        }
+    }
+
+    /// line_text returns the text of the line without its line break. In a file with mixed
+    /// line endings a '\r' before the '\n' belongs to the line break as well.
+    fn line_text(&self, line_index: usize) -> String {
+        let mut text = self.text.line(line_index).to_string();
+        if text.ends_with('\n') {
+            text.pop();
+            if self.line_ending == LineEnding::Mixed && text.ends_with('\r') {
+                text.pop();
+            }
+        }
+        text
+    }
+
+    /// clamp_location returns the nearest location that exists in the buffer.
+    pub fn clamp_location(&self, location: Location) -> Location {
+        let line_index = location.line_index.min(self.line_count().saturating_sub(1));
+        Location {
+            line_index,
+            grapheme_index: location
+                .grapheme_index
+                .min(self.line_grapheme_count(line_index)),
+        }
+    }
+
+    /// find_next returns the first match of the query at or after the given location, wrapping
+    /// around to the start of the buffer.
+    pub fn find_next(&self, query: &str, from: Location) -> Option<Location> {
+        let line_count = self.line_count();
+        if line_count == 0 {
+            return None;
+        }
+        for offset in 0..=line_count {
+            let line_index = from.line_index.saturating_add(offset) % line_count;
+            let start = if offset == 0 { from.grapheme_index } else { 0 };
+            if let Some(grapheme_index) = self.line(line_index)?.find(query, start) {
+                return Some(Location {
+                    line_index,
+                    grapheme_index,
+                });
+            }
+        }
+        None
+    }
+
+    /// find_previous returns the last match of the query before the given location, wrapping
+    /// around to the end of the buffer.
+    pub fn find_previous(&self, query: &str, from: Location) -> Option<Location> {
+        let line_count = self.line_count();
+        if line_count == 0 {
+            return None;
+        }
+        for offset in 0..=line_count {
+            let line_index = from
+                .line_index
+                .saturating_add(line_count.saturating_mul(2))
+                .saturating_sub(offset)
+                % line_count;
+            let before = if offset == 0 {
+                from.grapheme_index
+            } else {
+                usize::MAX
+            };
+            if let Some(grapheme_index) = self.line(line_index)?.rfind(query, before) {
+                return Some(Location {
+                    line_index,
+                    grapheme_index,
+                });
+            }
+        }
+        None
+    }
+
+    /// find_regex returns the first match of the regular expression at or after the given
+    /// location, without wrapping around. Matches never span lines, and their bounds are
+    /// rounded to whole graphemes.
+    pub fn find_regex(&self, regex: &Regex, template: &str, from: Location) -> Option<RegexMatch> {
+        for line_index in from.line_index..self.line_count() {
+            let text = self.line_text(line_index);
+            let grapheme_starts: Vec<usize> = text
+                .grapheme_indices(true)
+                .map(|(start, _)| start)
+                .collect();
+            let start_byte = if line_index == from.line_index {
+                if from.grapheme_index > grapheme_starts.len() {
+                    continue;
+                }
+                grapheme_starts
+                    .get(from.grapheme_index)
+                    .copied()
+                    .unwrap_or(text.len())
+            } else {
+                0
+            };
+            let Some(captures) = regex.captures_at(&text, start_byte) else {
+                continue;
+            };
+            let Some(whole) = captures.get(0) else {
+                continue;
+            };
+            let to_grapheme_index = |byte: usize| {
+                grapheme_starts
+                    .iter()
+                    .take_while(|&&start| start < byte)
+                    .count()
+            };
+            let mut replacement = String::new();
+            captures.expand(template, &mut replacement);
+            return Some(RegexMatch {
+                start: Location {
+                    line_index,
+                    grapheme_index: to_grapheme_index(whole.start()),
+                },
+                end: Location {
+                    line_index,
+                    grapheme_index: to_grapheme_index(whole.end()),
+                },
+                replacement,
+            });
+        }
+        None
+    }
+
+    pub fn line_grapheme_count(&self, line_index: usize) -> usize {
+        self.line(line_index)
+            .map_or(0, |line| line.grapheme_count())
+    }
+
+    pub fn insert_newline(&mut self, at: Location) -> Location {
+        self.insert_text(at, "\n")
+    }
+
+    /// insert_char inserts a typed character and returns the caret location after it.
+    /// Consecutive typed characters are undone together.
+    pub fn insert_char(&mut self, character: char, at: Location) -> Location {
+        if at.line_index > self.line_count() {
+            return at;
+        }
+        let appends_line = self.append_line_break(at);
+        let caret_after = self.insert_unrecorded(at, character.to_string().as_str());
+        self.history.record_typing(character, at, caret_after);
+        if appends_line {
+            self.history.record_line_break_before_last_edit(at);
         }
+        self.is_modified = true;
+        caret_after
     }
 
-    pub fn insert_char(&mut self, character: char, at: Location) {
-        let height = self.lines.len();
-        if at.line_index > height {
-            return;
+    /// insert_text inserts text which may span several lines as a single undo step and returns
+    /// the location right after the inserted text.
+    pub fn insert_text(&mut self, at: Location, text: &str) -> Location {
+        if at.line_index > self.line_count() || text.is_empty() {
+            return at;
         }
-        if at.line_index == height {
-            let line: Line = character.to_string().as_str().into();
-            self.lines.push(line);
-        } else if let Some(line) = self.lines.get_mut(at.line_index) {
-            line.insert_char(character, at.grapheme_index);
+        let appends_line = self.append_line_break(at);
+        let caret_after = self.insert_unrecorded(at, text);
+        let edit = Edit::Insert {
+            at,
+            text: text.to_string(),
+        };
+        self.history.record(edit, at, caret_after);
+        if appends_line {
+            self.history.record_line_break_before_last_edit(at);
         }
+        self.is_modified = true;
+        caret_after
     }
 
+    /// delete removes the grapheme at the given location, or joins the next line when the
+    /// location is at the end of a line.
     pub fn delete(&mut self, at: Location) {
-        if let Some(line) = self.lines.get(at.line_index) {
-            // If the caret position is at the end of the second last line
-            eprintln!(
-                "DEBUG: at.grapheme_index = {}, line.fragments.len() = {}",
-                at.grapheme_index,
-                line.fragments.len(),
-            );
-            if at.grapheme_index >= line.fragments.len()
-                && self.lines.len() > at.line_index.saturating_add(1)
-            {
-                let next_line = self.lines.remove(at.line_index.saturating_add(1));
-                // Merge the next line into the current line
-                self.lines[at.line_index].append(next_line);
-            } else if at.grapheme_index < line.fragments.len()
-                || at.grapheme_index == line.fragments.len()
-            {
-                self.lines[at.line_index].delete(at.grapheme_index);
+        if let Some(to) = self.next_location(at) {
+            self.delete_recorded(at, to, at, at);
+        }
+    }
+
+    /// backspace removes the grapheme before the given location, or joins the line with the
+    /// previous one when the location is at the start of a line. It returns the new caret.
+    pub fn backspace(&mut self, at: Location) -> Location {
+        match self.previous_location(at) {
+            Some(from) => {
+                self.delete_recorded(from, at, at, from);
+                from
+            }
+            None => at,
+        }
+    }
+
+    /// delete_range removes the text between two locations and returns the location the text
+    /// started at.
+    pub fn delete_range(&mut self, from: Location, to: Location) -> Location {
+        self.delete_recorded(from, to, to, from);
+        from
+    }
+
+    /// delete_word_before removes everything from the start of the word before the given
+    /// location up to it, and returns the new caret.
+    pub fn delete_word_before(&mut self, at: Location) -> Location {
+        let from = self.previous_word_start(at);
+        self.delete_recorded(from, at, at, from);
+        from
+    }
+
+    /// delete_word_after removes everything from the given location to the end of the word
+    /// after it.
+    pub fn delete_word_after(&mut self, at: Location) {
+        let to = self.next_word_end(at);
+        self.delete_recorded(at, to, at, at);
+    }
+
+    /// previous_word_start returns the start of the word before the given location. At the
+    /// start of a line it returns the end of the previous line.
+    pub fn previous_word_start(&self, at: Location) -> Location {
+        match self.line(at.line_index) {
+            Some(line) if at.grapheme_index > 0 => Location {
+                line_index: at.line_index,
+                grapheme_index: line.previous_word_start(at.grapheme_index),
+            },
+            _ => self.previous_location(at).unwrap_or(at),
+        }
+    }
+
+    /// next_word_end returns the end of the word after the given location. At the end of a
+    /// line it returns the start of the next line.
+    pub fn next_word_end(&self, at: Location) -> Location {
+        match self.line(at.line_index) {
+            Some(line) if at.grapheme_index < line.grapheme_count() => Location {
+                line_index: at.line_index,
+                grapheme_index: line.next_word_end(at.grapheme_index),
+            },
+            _ => self.next_location(at).unwrap_or(at),
+        }
+    }
+
+    /// replace_range replaces the text between two locations as a single undo step and returns
+    /// the location right after the new text.
+    pub fn replace_range(&mut self, from: Location, to: Location, text: &str) -> Location {
+        self.history.start_group();
+        self.delete_recorded(from, to, from, from);
+        let caret_after = self.insert_text(from, text);
+        self.history.end_group();
+        caret_after
+    }
+
+    /// undo reverts the last undo step and returns where the caret was before it.
+    pub fn undo(&mut self) -> Option<Location> {
+        let step = self.history.pop_undo()?;
+        for edit in step.edits.iter().rev() {
+            match edit {
+                Edit::Insert { at, text } => {
+                    let to = Self::location_after(*at, text);
+                    self.delete_unrecorded(*at, to);
+                }
+                Edit::Delete { at, text } => {
+                    self.insert_unrecorded(*at, text);
+                }
             }
         }
+        let caret = step.caret_before;
+        self.history.push_redo(step);
+        self.is_modified = !self.history.is_at_save_point();
+        Some(caret)
+    }
+
+    /// redo reapplies the last undone step and returns where the caret was after it.
+    pub fn redo(&mut self) -> Option<Location> {
+        let step = self.history.pop_redo()?;
+        for edit in &step.edits {
+            match edit {
+                Edit::Insert { at, text } => {
+                    self.insert_unrecorded(*at, text);
+                }
+                Edit::Delete { at, text } => {
+                    let to = Self::location_after(*at, text);
+                    self.delete_unrecorded(*at, to);
+                }
+            }
+        }
+        let caret = step.caret_after;
+        self.history.push_undo(step);
+        self.is_modified = !self.history.is_at_save_point();
+        Some(caret)
+    }
+
+    fn delete_recorded(
+        &mut self,
+        from: Location,
+        to: Location,
+        caret_before: Location,
+        caret_after: Location,
+    ) -> String {
+        let text = self.delete_unrecorded(from, to);
+        if !text.is_empty() {
+            let edit = Edit::Delete {
+                at: from,
+                text: text.clone(),
+            };
+            self.history.record(edit, caret_before, caret_after);
+            self.is_modified = true;
+        }
+        text
+    }
+
+    /// append_line_break adds an empty line when the location is right after the last line, so
+    /// that text inserted there forms a line of its own. It returns whether it did.
+    fn append_line_break(&mut self, at: Location) -> bool {
+        if at.line_index != self.line_count() {
+            return false;
+        }
+        self.insert_unrecorded(at, "\n");
+        true
+    }
+
+    /// insert_unrecorded inserts the text into the rope without touching the history and
+    /// returns the location right after it.
+    fn insert_unrecorded(&mut self, at: Location, text: &str) -> Location {
+        let Some(char_index) = self.char_index(at) else {
+            return at;
+        };
+        let inserted_chars = text.chars().count();
+        self.text.insert(char_index, text);
+        self.revision = self.revision.wrapping_add(1);
+        self.location_of(char_index.saturating_add(inserted_chars))
+    }
+
+    /// delete_unrecorded removes the text between two locations without touching the history
+    /// and returns it, with line breaks as '\n'.
+    fn delete_unrecorded(&mut self, from: Location, to: Location) -> String {
+        let line_count = self.line_count();
+        // The end may be right after the last line, which removes the final line break
+        if from.line_index >= line_count || to.line_index > line_count {
+            return String::new();
+        }
+        let (Some(start), Some(end)) = (self.char_index(from), self.char_index(to)) else {
+            return String::new();
+        };
+        if end <= start {
+            return String::new();
+        }
+        let removed = self.text.slice(start..end).to_string();
+        self.text.remove(start..end);
+        self.revision = self.revision.wrapping_add(1);
+        removed
+    }
+
+    /// char_index converts a location into an index into the rope. The location right after
+    /// the last line maps to the end of the rope.
+    fn char_index(&self, at: Location) -> Option<usize> {
+        let line_count = self.line_count();
+        if at.line_index > line_count {
+            return None;
+        }
+        let line_start = self.text.line_to_char(at.line_index);
+        if at.line_index == line_count {
+            return Some(line_start);
+        }
+        let chars_before: usize = self
+            .line_text(at.line_index)
+            .graphemes(true)
+            .take(at.grapheme_index)
+            .map(|grapheme| grapheme.chars().count())
+            .sum();
+        Some(line_start.saturating_add(chars_before))
+    }
+
+    /// location_of converts an index into the rope back into a location.
+    fn location_of(&self, char_index: usize) -> Location {
+        let line_index = self.text.char_to_line(char_index);
+        let line_start = self.text.line_to_char(line_index);
+        let before = self.text.slice(line_start..char_index).to_string();
+        Location {
+            line_index,
+            grapheme_index: before.graphemes(true).count(),
+        }
+    }
+
+    /// next_location returns the location one grapheme after the given one, crossing into the
+    /// next line at the end of a line.
+    fn next_location(&self, at: Location) -> Option<Location> {
+        let line = self.line(at.line_index)?;
+        if at.grapheme_index < line.grapheme_count() {
+            Some(Location {
+                line_index: at.line_index,
+                grapheme_index: at.grapheme_index.saturating_add(1),
+            })
+        } else if at.line_index.saturating_add(1) < self.line_count() {
+            Some(Location {
+                line_index: at.line_index.saturating_add(1),
+                grapheme_index: 0,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// previous_location returns the location one grapheme before the given one, crossing
+    /// into the end of the previous line at the start of a line.
+    fn previous_location(&self, at: Location) -> Option<Location> {
+        if at.grapheme_index > 0 {
+            let line_len = self.line(at.line_index)?.grapheme_count();
+            Some(Location {
+                line_index: at.line_index,
+                grapheme_index: at.grapheme_index.min(line_len).saturating_sub(1),
+            })
+        } else if at.line_index > 0 {
+            let line_index = at.line_index.saturating_sub(1);
+            Some(Location {
+                line_index,
+                grapheme_index: self.line_grapheme_count(line_index),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// location_after returns the location right after `text` if it was inserted at `at`.
+    fn location_after(at: Location, text: &str) -> Location {
+        match text.rsplit_once('\n') {
+            Some((_, last)) => Location {
+                line_index: at.line_index.saturating_add(text.matches('\n').count()),
+                grapheme_index: last.graphemes(true).count(),
+            },
+            None => Location {
+                line_index: at.line_index,
+                grapheme_index: at
+                    .grapheme_index
+                    .saturating_add(text.graphemes(true).count()),
+            },
+        }
     }
 }
 
 impl Default for Buffer {
     fn default() -> Self {
         Self {
-            lines: vec!["Hello, World!".into()],
+            text: Rope::from_str("Hello, World!\n"),
+            file_path: None,
+            is_modified: false,
+            line_ending: LineEnding::default(),
+            has_trailing_newline: true,
+            has_bom: false,
+            history: History::default(),
+            indentation: Indentation::default(),
+            revision: 0,
+        }
+    }
+}
+
+/// live_swap_owner returns the process that wrote the swap file if it is another session
+/// that is still running.
+fn live_swap_owner(swap_path: &Path) -> Option<u32> {
+    let pid = swap_owner(swap_path)?;
+    if pid == process::id() {
+        return None;
+    }
+    // Signal 0 only checks whether the process exists. It may belong to another user.
+    match wrap_with_result(unsafe { libc::kill(libc::pid_t::try_from(pid).ok()?, 0) }) {
+        Ok(_) => Some(pid),
+        Err(err) if err.raw_os_error() == Some(libc::EPERM) => Some(pid),
+        Err(_) => None,
+    }
+}
+
+/// swap_owner returns the process named in the header of the swap file.
+fn swap_owner(swap_path: &Path) -> Option<u32> {
+    let swap = fs::read_to_string(swap_path).ok()?;
+    let (pid, _) = swap.strip_prefix(SWAP_HEADER)?.split_once('\n')?;
+    pid.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(line_index: usize, grapheme_index: usize) -> Location {
+        Location {
+            line_index,
+            grapheme_index,
+        }
+    }
+
+    #[test]
+    fn contents_reproduces_the_loaded_text() {
+        let texts = [
+            "",
+            "one\ntwo\n",
+            "one\r\ntwo\r\n",
+            "one\r\ntwo\nthree\r\n",
+            "one\ntwo",
+            "one\r\ntwo",
+            "one\r\ntwo\nthree",
+            "\u{feff}one\ntwo\n",
+            "\u{feff}one\r\ntwo",
+        ];
+        for text in texts {
+            assert_eq!(Buffer::new(text.to_string()).contents(), text);
         }
     }
+
+    #[test]
+    fn new_lines_take_the_line_ending_of_the_file() {
+        let mut buffer = Buffer::new("one\r\ntwo\r\n".to_string());
+        buffer.insert_newline(at(0, 3));
+        assert_eq!(buffer.contents(), "one\r\n\r\ntwo\r\n");
+
+        let mut buffer = Buffer::new("one\r\ntwo\n".to_string());
+        buffer.insert_newline(at(1, 3));
+        assert_eq!(buffer.contents(), "one\r\ntwo\n\n");
+    }
+
+    #[test]
+    fn lines_of_a_mixed_file_hide_the_carriage_return() {
+        let mut buffer = Buffer::new("one\r\ntwo\n".to_string());
+        assert_eq!(buffer.line(0).unwrap().to_string(), "one");
+        buffer.insert_char('!', at(0, 3));
+        assert_eq!(buffer.contents(), "one!\r\ntwo\n");
+    }
+
+    #[test]
+    fn undo_removes_the_line_typed_into_an_empty_buffer() {
+        let mut buffer = Buffer::new(String::new());
+        buffer.insert_char('a', at(0, 0));
+        assert_eq!(buffer.contents(), "a\n");
+
+        buffer.undo();
+        assert_eq!(buffer.contents(), "");
+        assert!(!buffer.is_modified);
+        buffer.redo();
+        assert_eq!(buffer.contents(), "a\n");
+    }
+
+    #[test]
+    fn undo_removes_text_appended_after_the_last_line() {
+        let mut buffer = Buffer::new("one\n".to_string());
+        let caret = buffer.insert_text(at(1, 0), "two\nthree");
+        assert_eq!(caret, at(2, 5));
+        assert_eq!(buffer.contents(), "one\ntwo\nthree\n");
+
+        buffer.undo();
+        assert_eq!(buffer.contents(), "one\n");
+        buffer.redo();
+        assert_eq!(buffer.contents(), "one\ntwo\nthree\n");
+    }
+
+    #[test]
+    fn undo_restores_a_deleted_last_line() {
+        let mut buffer = Buffer::new("one\ntwo\n".to_string());
+        buffer.delete_range(at(1, 0), at(2, 0));
+        assert_eq!(buffer.contents(), "one\n");
+
+        buffer.undo();
+        assert_eq!(buffer.contents(), "one\ntwo\n");
+    }
 }
@@ -1,3 +1,4 @@
 pub enum EditorCommand {
     Quit,
+    Save,
 }
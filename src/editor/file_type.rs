@@ -0,0 +1,71 @@
+use std::{fmt::Display, path::Path};
+
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub enum FileType {
+    Rust,
+    C,
+    Cpp,
+    Go,
+    Python,
+    JavaScript,
+    TypeScript,
+    Shell,
+    Html,
+    Css,
+    Json,
+    Toml,
+    Yaml,
+    Markdown,
+    #[default]
+    Text,
+}
+
+impl FileType {
+    /// detect guesses the file type from the extension of the path.
+    pub fn detect(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "rs" => FileType::Rust,
+            "c" | "h" => FileType::C,
+            "cc" | "cpp" | "cxx" | "hh" | "hpp" => FileType::Cpp,
+            "go" => FileType::Go,
+            "py" => FileType::Python,
+            "js" | "mjs" | "cjs" | "jsx" => FileType::JavaScript,
+            "ts" | "tsx" => FileType::TypeScript,
+            "sh" | "bash" | "zsh" => FileType::Shell,
+            "html" | "htm" => FileType::Html,
+            "css" => FileType::Css,
+            "json" => FileType::Json,
+            "toml" => FileType::Toml,
+            "yml" | "yaml" => FileType::Yaml,
+            "md" | "markdown" => FileType::Markdown,
+            _ => FileType::Text,
+        }
+    }
+}
+
+impl Display for FileType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            FileType::Rust => "Rust",
+            FileType::C => "C",
+            FileType::Cpp => "C++",
+            FileType::Go => "Go",
+            FileType::Python => "Python",
+            FileType::JavaScript => "JavaScript",
+            FileType::TypeScript => "TypeScript",
+            FileType::Shell => "Shell",
+            FileType::Html => "HTML",
+            FileType::Css => "CSS",
+            FileType::Json => "JSON",
+            FileType::Toml => "TOML",
+            FileType::Yaml => "YAML",
+            FileType::Markdown => "Markdown",
+            FileType::Text => "Text",
+        };
+        write!(f, "{name}")
+    }
+}
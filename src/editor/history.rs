@@ -0,0 +1,321 @@
+use crate::view::Location;
+
+/// How many undo steps are kept before the oldest ones are dropped
+const MAX_UNDO_STEPS: usize = 1000;
+
+/// Edit is a single reversible change to the buffer.
+/// The text may span several lines, which are separated by '\n'.
+#[derive(Clone, Debug)]
+pub enum Edit {
+    Insert { at: Location, text: String },
+    Delete { at: Location, text: String },
+}
+
+/// Step is what a single undo reverts or a single redo reapplies.
+#[derive(Clone, Debug)]
+pub struct Step {
+    pub edits: Vec<Edit>,
+    pub caret_before: Location,
+    pub caret_after: Location,
+    // Typed characters are merged into this step until a word ends
+    is_typing: bool,
+    // Tells the step apart from all others, so that the save point survives undo and redo
+    id: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct History {
+    undo_stack: Vec<Step>,
+    redo_stack: Vec<Step>,
+    // Edits recorded while a group is open end up in a single step
+    group_depth: usize,
+    group_has_step: bool,
+    last_step_id: usize,
+    // Id of the last applied step when the buffer was saved, 0 before the first step. None once
+    // the saved text can no longer be reached by undo or redo.
+    save_point: Option<usize>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            group_depth: 0,
+            group_has_step: false,
+            last_step_id: 0,
+            save_point: Some(0),
+        }
+    }
+}
+
+impl History {
+    pub fn record(&mut self, edit: Edit, caret_before: Location, caret_after: Location) {
+        self.redo_stack.clear();
+        if self.group_depth > 0
+            && self.group_has_step
+            && let Some(step) = self.undo_stack.last_mut()
+        {
+            step.edits.push(edit);
+            step.caret_after = caret_after;
+            return;
+        }
+        self.group_has_step = self.group_depth > 0;
+        let id = self.next_step_id();
+        self.push(Step {
+            edits: vec![edit],
+            caret_before,
+            caret_after,
+            is_typing: false,
+            id,
+        });
+    }
+
+    /// record_typing records a typed character, grouping it with the characters typed right
+    /// before it. Whitespace closes the group so that undo works word by word. Inside an open
+    /// group the character belongs to that group instead.
+    pub fn record_typing(&mut self, character: char, at: Location, caret_after: Location) {
+        if self.group_depth > 0 {
+            let edit = Edit::Insert {
+                at,
+                text: character.to_string(),
+            };
+            self.record(edit, at, caret_after);
+            return;
+        }
+        self.redo_stack.clear();
+        if let Some(step) = self.undo_stack.last_mut()
+            && step.is_typing
+            && step.caret_after == at
+            && let Some(Edit::Insert { text, .. }) = step.edits.last_mut()
+        {
+            text.push(character);
+            step.caret_after = caret_after;
+            step.is_typing = !character.is_whitespace();
+            return;
+        }
+        let id = self.next_step_id();
+        self.push(Step {
+            edits: vec![Edit::Insert {
+                at,
+                text: character.to_string(),
+            }],
+            caret_before: at,
+            caret_after,
+            is_typing: !character.is_whitespace(),
+            id,
+        });
+    }
+
+    /// record_line_break_before_last_edit records the line break that was inserted at the given
+    /// location right before the last recorded edit, as part of the same step. Text inserted
+    /// after the last line needs one to form a line of its own.
+    pub fn record_line_break_before_last_edit(&mut self, at: Location) {
+        if let Some(step) = self.undo_stack.last_mut() {
+            let index = step.edits.len().saturating_sub(1);
+            let edit = Edit::Insert {
+                at,
+                text: "\n".to_string(),
+            };
+            step.edits.insert(index, edit);
+        }
+    }
+
+    /// start_group makes every edit until the matching end_group part of one undo step.
+    /// Groups can be nested; only the outermost one counts.
+    pub fn start_group(&mut self) {
+        if self.group_depth == 0 {
+            self.group_has_step = false;
+        }
+        self.group_depth = self.group_depth.saturating_add(1);
+    }
+
+    pub fn end_group(&mut self) {
+        self.group_depth = self.group_depth.saturating_sub(1);
+    }
+
+    /// seal stops the current typing group, so that the next typed character starts a new step.
+    pub fn seal(&mut self) {
+        if let Some(step) = self.undo_stack.last_mut() {
+            step.is_typing = false;
+        }
+    }
+
+    /// mark_saved remembers the current step as the one the file on disk matches. Typing after
+    /// saving starts a new step, so that undo can return to exactly the saved text.
+    pub fn mark_saved(&mut self) {
+        self.seal();
+        self.save_point = Some(self.current_step_id());
+    }
+
+    /// forget_save_point is used when the text changes in a way that undo cannot revert.
+    pub fn forget_save_point(&mut self) {
+        self.save_point = None;
+    }
+
+    /// is_at_save_point tells whether undo and redo have led back to the saved text.
+    pub fn is_at_save_point(&self) -> bool {
+        self.save_point == Some(self.current_step_id())
+    }
+
+    fn current_step_id(&self) -> usize {
+        self.undo_stack.last().map_or(0, |step| step.id)
+    }
+
+    fn next_step_id(&mut self) -> usize {
+        self.last_step_id = self.last_step_id.saturating_add(1);
+        self.last_step_id
+    }
+
+    pub fn pop_undo(&mut self) -> Option<Step> {
+        self.undo_stack.pop()
+    }
+
+    pub fn pop_redo(&mut self) -> Option<Step> {
+        self.redo_stack.pop()
+    }
+
+    /// push_undo puts back a step that was redone.
+    pub fn push_undo(&mut self, mut step: Step) {
+        // A redone step must not swallow new typing
+        step.is_typing = false;
+        self.push(step);
+    }
+
+    pub fn push_redo(&mut self, step: Step) {
+        self.redo_stack.push(step);
+    }
+
+    fn push(&mut self, step: Step) {
+        self.seal();
+        if self.undo_stack.len() >= MAX_UNDO_STEPS {
+            let dropped = self.undo_stack.remove(0);
+            // Undoing everything now ends after the dropped step instead of before it
+            self.save_point = match self.save_point {
+                Some(0) => None,
+                Some(id) if id == dropped.id => Some(0),
+                save_point => save_point,
+            };
+        }
+        self.undo_stack.push(step);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(grapheme_index: usize) -> Location {
+        Location {
+            line_index: 0,
+            grapheme_index,
+        }
+    }
+
+    fn type_text(history: &mut History, text: &str, from: usize) {
+        for (offset, character) in text.chars().enumerate() {
+            let index = from.saturating_add(offset);
+            history.record_typing(character, at(index), at(index.saturating_add(1)));
+        }
+    }
+
+    fn inserted_text(step: &Step) -> String {
+        step.edits
+            .iter()
+            .map(|edit| match edit {
+                Edit::Insert { text, .. } => text.as_str(),
+                Edit::Delete { .. } => "",
+            })
+            .collect()
+    }
+
+    #[test]
+    fn typing_is_undone_word_by_word() {
+        let mut history = History::default();
+        type_text(&mut history, "hello world", 0);
+
+        let last = history.pop_undo().unwrap();
+        assert_eq!(inserted_text(&last), "world");
+        let first = history.pop_undo().unwrap();
+        assert_eq!(inserted_text(&first), "hello ");
+        assert_eq!(first.caret_before, at(0));
+        assert!(history.pop_undo().is_none());
+    }
+
+    #[test]
+    fn seal_starts_a_new_typing_step() {
+        let mut history = History::default();
+        type_text(&mut history, "ab", 0);
+        history.seal();
+        type_text(&mut history, "cd", 2);
+
+        assert_eq!(inserted_text(&history.pop_undo().unwrap()), "cd");
+        assert_eq!(inserted_text(&history.pop_undo().unwrap()), "ab");
+    }
+
+    #[test]
+    fn typing_elsewhere_starts_a_new_step() {
+        let mut history = History::default();
+        type_text(&mut history, "ab", 0);
+        type_text(&mut history, "x", 5);
+
+        assert_eq!(inserted_text(&history.pop_undo().unwrap()), "x");
+        assert_eq!(inserted_text(&history.pop_undo().unwrap()), "ab");
+    }
+
+    #[test]
+    fn group_makes_one_step() {
+        let mut history = History::default();
+        history.start_group();
+        history.record(
+            Edit::Delete {
+                at: at(0),
+                text: "old".to_string(),
+            },
+            at(3),
+            at(0),
+        );
+        type_text(&mut history, "n", 0);
+        history.end_group();
+
+        let step = history.pop_undo().unwrap();
+        assert_eq!(step.edits.len(), 2);
+        assert_eq!(step.caret_before, at(3));
+        assert_eq!(step.caret_after, at(1));
+        assert!(history.pop_undo().is_none());
+    }
+
+    #[test]
+    fn save_point_follows_undo_and_redo() {
+        let mut history = History::default();
+        assert!(history.is_at_save_point());
+        type_text(&mut history, "ab", 0);
+        history.mark_saved();
+        type_text(&mut history, "c", 2);
+        assert!(!history.is_at_save_point());
+
+        let step = history.pop_undo().unwrap();
+        history.push_redo(step);
+        assert!(history.is_at_save_point());
+        let step = history.pop_undo().unwrap();
+        history.push_redo(step);
+        assert!(!history.is_at_save_point());
+
+        let step = history.pop_redo().unwrap();
+        history.push_undo(step);
+        assert!(history.is_at_save_point());
+    }
+
+    #[test]
+    fn recording_after_undo_drops_the_save_point() {
+        let mut history = History::default();
+        type_text(&mut history, "a", 0);
+        history.mark_saved();
+        let step = history.pop_undo().unwrap();
+        history.push_redo(step);
+        type_text(&mut history, "b", 0);
+
+        assert!(!history.is_at_save_point());
+        assert!(history.pop_redo().is_none());
+    }
+}
@@ -0,0 +1,27 @@
+/// Columns between two tab stops unless configured otherwise, as in most terminal tools
+pub const DEFAULT_TAB_WIDTH: usize = 8;
+
+/// Indentation is how the buffer displays and inserts tabs.
+#[derive(Clone, Copy, Debug)]
+pub struct Indentation {
+    pub tab_width: usize,
+    // Insert spaces up to the next tab stop instead of a tab character
+    pub expand_tabs: bool,
+}
+
+impl Indentation {
+    /// spaces_to_next_tab_stop returns how many columns a tab at the given column takes.
+    pub fn spaces_to_next_tab_stop(&self, column: usize) -> usize {
+        let tab_width = self.tab_width.max(1);
+        tab_width.saturating_sub(column % tab_width)
+    }
+}
+
+impl Default for Indentation {
+    fn default() -> Self {
+        Self {
+            tab_width: DEFAULT_TAB_WIDTH,
+            expand_tabs: false,
+        }
+    }
+}
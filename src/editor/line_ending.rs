@@ -0,0 +1,45 @@
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+    // Both occur. The text is kept as it is, so every '\r' stays at the end of its line.
+    Mixed,
+}
+
+impl LineEnding {
+    /// detect picks the line ending style shared by all line breaks in the text.
+    /// Text without any line break falls back to the default.
+    pub fn detect(text: &str) -> Self {
+        let crlf_count = text.matches("\r\n").count();
+        if crlf_count == 0 {
+            LineEnding::Lf
+        } else if crlf_count == text.matches('\n').count() {
+            LineEnding::CrLf
+        } else {
+            LineEnding::Mixed
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            // New lines of a mixed file end in a bare LF
+            LineEnding::Lf | LineEnding::Mixed => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_tells_the_line_ending_styles_apart() {
+        assert_eq!(LineEnding::detect("a\nb\n"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("a\r\nb\r\n"), LineEnding::CrLf);
+        assert_eq!(LineEnding::detect("a\r\nb\n"), LineEnding::Mixed);
+        assert_eq!(LineEnding::detect("no line break"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect(""), LineEnding::Lf);
+    }
+}
@@ -1,23 +1,111 @@
+pub(crate) mod backup;
 pub(crate) mod editor;
 pub(crate) mod view;
 
-use crate::editor::Editor;
+use crate::{
+    editor::{Editor, buffer::Buffer, indentation::Indentation},
+    view::{Location, document::Document, osc52::Osc52},
+};
+use std::{io, path::Path, process};
+
+/// split_location splits `path:line` or `path:line:column`, as printed by compilers and grep,
+/// into the path and the location. A file whose name really contains the suffix wins.
+fn split_location(arg: &str) -> (&str, Option<Location>) {
+    if Path::new(arg).exists() {
+        return (arg, None);
+    }
+    let trimmed = arg.trim_end_matches(':');
+    let colons: Vec<usize> = trimmed
+        .rmatch_indices(':')
+        .map(|(index, _)| index)
+        .take(2)
+        .collect();
+    // Try path:line:column before path:line
+    for &colon in colons.iter().rev() {
+        let (path, suffix) = (&trimmed[..colon], &trimmed[colon.saturating_add(1)..]);
+        if !path.is_empty()
+            && let Some(location) = Location::parse_line_column(suffix)
+        {
+            return (path, Some(location));
+        }
+    }
+    (arg, None)
+}
 
 fn main() {
-    let args: Vec<String> = std::env::args().collect();
-    let mut file_content = String::new();
-    if let Some(arg1) = args.get(1) {
-        match std::fs::read_to_string(arg1) {
-            Ok(content) => {
-                file_content = content;
+    let mut indentation = Indentation::default();
+    let mut osc52 = Osc52::default();
+    let mut file_args = Vec::new();
+    // Set by +line, for the file that follows it
+    let mut location = None;
+    for arg in std::env::args().skip(1) {
+        if let Some(tab_width) = arg.strip_prefix("--tab-width=") {
+            match tab_width.parse() {
+                Ok(tab_width) if tab_width > 0 => indentation.tab_width = tab_width,
+                _ => {
+                    eprintln!("Invalid tab width: {tab_width}");
+                    process::exit(1);
+                }
             }
-            Err(e) => {
-                eprintln!("Error reading file {}: {}", arg1, e);
+        } else if arg == "--expand-tabs" {
+            indentation.expand_tabs = true;
+        } else if arg == "--no-osc52" {
+            osc52.enabled = false;
+        } else if let Some(max_bytes) = arg.strip_prefix("--osc52-limit=") {
+            match max_bytes.parse() {
+                Ok(max_bytes) => osc52.max_bytes = max_bytes,
+                Err(_) => {
+                    eprintln!("Invalid clipboard size limit: {max_bytes}");
+                    process::exit(1);
+                }
+            }
+        } else if let Some(line_column) = arg.strip_prefix('+') {
+            match Location::parse_line_column(line_column) {
+                Some(line_location) => location = Some(line_location),
+                None => {
+                    eprintln!("Invalid line number: {arg}");
+                    process::exit(1);
+                }
             }
+        } else {
+            file_args.push((arg, location.take()));
         }
     }
 
-    let terminal = view::terminal::Terminal::new(file_content);
+    let mut documents: Vec<Document> = Vec::new();
+    for (arg, line_location) in file_args {
+        let (path, path_location) = split_location(&arg);
+        // Two buffers for the same file would overwrite each other's changes
+        if documents
+            .iter()
+            .any(|document| document.buffer.is_bound_to(Path::new(path)))
+        {
+            continue;
+        }
+        let mut buffer = match Buffer::load(path) {
+            Ok(loaded) => loaded,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                // Keep the path so that saving creates the file
+                let mut buffer = Buffer::new(String::new());
+                buffer.file_path = Some(path.into());
+                buffer
+            }
+            Err(e) => {
+                eprintln!("Could not open {path}: {e}");
+                process::exit(1);
+            }
+        };
+        buffer.indentation = indentation;
+        let location = path_location.or(line_location).unwrap_or_default();
+        documents.push(Document::new(buffer, location));
+    }
+    if documents.is_empty() {
+        let mut buffer = Buffer::new(String::new());
+        buffer.indentation = indentation;
+        documents.push(Document::new(buffer, Location::default()));
+    }
+
+    let terminal = view::terminal::Terminal::new(documents, osc52);
     let mut editor = Editor::new(terminal);
     if let Err(e) = editor.run() {
         eprintln!("Error: {e}");
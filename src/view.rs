@@ -1,11 +1,20 @@
+pub(crate) mod document;
+pub(crate) mod gutter;
+pub(crate) mod kill_ring;
+pub(crate) mod message_bar;
+pub(crate) mod osc52;
+pub(crate) mod prompt;
+pub(crate) mod replace;
+pub(crate) mod status_bar;
 pub(crate) mod terminal;
 pub(crate) mod terminal_command;
 
-use crate::editor::editor_command::EditorCommand;
+use crate::editor::{editor_command::EditorCommand, indentation::Indentation};
 use crate::view::terminal_command::{Direction, TerminalCommand};
 use std::fmt::Display;
 use std::io;
 use std::ops::Range;
+use std::path::PathBuf;
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
@@ -29,28 +38,67 @@ where
     fn evaluate_keypress<F>(&mut self, action: F) -> io::Result<()>
     where
         F: FnMut(EditorCommand);
-    fn get_position(&mut self) -> io::Result<Position>;
+    /// get_position returns where the caret is drawn, or None while it is scrolled out of view.
+    fn get_position(&mut self) -> io::Result<Option<Position>>;
+    /// save writes the buffer to its file. Failures are reported to the user instead of being
+    /// returned, so that a failed save never ends the session and loses the buffer.
+    fn save(&mut self);
+    fn write_swap(&self) -> io::Result<Vec<PathBuf>>;
 }
 
 /// Location is the absolute coordinates in the document
 /// Location is measured in graphemes
-#[derive(Clone, Copy, Default, Debug)]
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Location {
     pub line_index: usize,     // Line number in the document (row)
     pub grapheme_index: usize, // Grapheme index within that line (column)
 }
 
 impl Location {
-    pub fn to_position(&self, scroll_offset: Location) -> Position {
+    /// parse_line_column reads a 1-based `line` or `line:column` as typed by the user or
+    /// printed by compilers. Zero is treated like 1.
+    pub fn parse_line_column(text: &str) -> Option<Self> {
+        let (line, column) = match text.trim().split_once(':') {
+            Some((line, column)) => (line, Some(column)),
+            None => (text.trim(), None),
+        };
+        let line_number: usize = line.parse().ok()?;
+        let column_number: usize = match column {
+            Some(column) => column.parse().ok()?,
+            None => 1,
+        };
+        Some(Self {
+            line_index: line_number.saturating_sub(1),
+            grapheme_index: column_number.saturating_sub(1),
+        })
+    }
+
+    /// to_position returns where the location in the given line is drawn, with the text
+    /// starting right after the gutter.
+    pub fn to_position(
+        self,
+        line: &Line,
+        scroll_offset: ScrollOffset,
+        gutter_width: usize,
+    ) -> Position {
         Position {
-            x: self
-                .grapheme_index
-                .saturating_sub(scroll_offset.grapheme_index),
+            x: line
+                .column_of(self.grapheme_index)
+                .saturating_sub(scroll_offset.column)
+                .saturating_add(gutter_width),
             y: self.line_index.saturating_sub(scroll_offset.line_index),
         }
     }
 }
 
+/// ScrollOffset is the part of the document scrolled out of view above and left of the viewport
+/// Unlike a location, its column is measured in screen cells
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct ScrollOffset {
+    pub line_index: usize, // First line shown in the viewport
+    pub column: usize,     // First screen column of the text shown in the viewport
+}
+
 /// Position is the absolute coordinates in the rendered viewport
 /// Position is measured in screen cells
 #[derive(Clone, Copy, Default)]
@@ -85,13 +133,109 @@ impl Line {
     }
 
     pub fn graphemes_width(&self) -> usize {
+        self.fragments.iter().fold(0, |width, fragment| {
+            fragment.rendered_width.saturating_add(width)
+        })
+    }
+
+    /// column_of returns the screen column at which the grapheme with the given index starts,
+    /// which is the rendered width of the graphemes before it.
+    pub fn column_of(&self, grapheme_index: usize) -> usize {
         self.fragments
             .iter()
-            .map(|text_fragment| match text_fragment.rendered_width {
-                GraphemeWidth::Half => 1,
-                GraphemeWidth::Full => 2,
+            .take(grapheme_index)
+            .fold(0, |width, fragment| {
+                fragment.rendered_width.saturating_add(width)
             })
-            .sum()
+    }
+
+    /// grapheme_index_at returns the index of the grapheme covering the given screen column.
+    /// A column inside a wide grapheme or a tab maps to that grapheme, and a column past the
+    /// end of the line maps to the end of the line.
+    pub fn grapheme_index_at(&self, column: usize) -> usize {
+        let mut fragment_start: usize = 0;
+        for (index, fragment) in self.fragments.iter().enumerate() {
+            let fragment_end = fragment.rendered_width.saturating_add(fragment_start);
+            if column < fragment_end {
+                return index;
+            }
+            fragment_start = fragment_end;
+        }
+        self.grapheme_count()
+    }
+
+    /// find returns the grapheme index of the first occurrence of the query at or after `from`.
+    /// The query is compared grapheme by grapheme, so it never matches half a grapheme.
+    pub fn find(&self, query: &str, from: usize) -> Option<usize> {
+        let query: Vec<&str> = query.graphemes(true).collect();
+        let last_start = self.fragments.len().checked_sub(query.len())?;
+        if query.is_empty() {
+            return None;
+        }
+        (from..=last_start).find(|&start| self.matches_at(&query, start))
+    }
+
+    /// rfind returns the grapheme index of the last occurrence of the query that starts
+    /// before `before`.
+    pub fn rfind(&self, query: &str, before: usize) -> Option<usize> {
+        let query: Vec<&str> = query.graphemes(true).collect();
+        let last_start = self.fragments.len().checked_sub(query.len())?;
+        if query.is_empty() || before == 0 {
+            return None;
+        }
+        (0..=last_start.min(before.saturating_sub(1)))
+            .rev()
+            .find(|&start| self.matches_at(&query, start))
+    }
+
+    /// previous_word_start returns the grapheme index where the last word starting before
+    /// `before` begins, or 0 if there is none.
+    pub fn previous_word_start(&self, before: usize) -> usize {
+        self.word_ranges()
+            .iter()
+            .rev()
+            .find(|word| word.start < before)
+            .map_or(0, |word| word.start)
+    }
+
+    /// next_word_end returns the grapheme index where the first word ending after `after`
+    /// ends, or the end of the line if there is none.
+    pub fn next_word_end(&self, after: usize) -> usize {
+        self.word_ranges()
+            .iter()
+            .find(|word| word.end > after)
+            .map_or(self.grapheme_count(), |word| word.end)
+    }
+
+    /// word_at returns the grapheme range of the word covering the given grapheme index.
+    pub fn word_at(&self, grapheme_index: usize) -> Option<Range<usize>> {
+        self.word_ranges()
+            .into_iter()
+            .find(|word| word.contains(&grapheme_index))
+    }
+
+    /// word_ranges returns the grapheme ranges of the words in the line, following the Unicode
+    /// word boundaries. Whitespace and punctuation between words are not part of any word.
+    fn word_ranges(&self) -> Vec<Range<usize>> {
+        let text = self.to_string();
+        let mut ranges = Vec::new();
+        let mut start: usize = 0;
+        for segment in text.split_word_bounds() {
+            let end = start.saturating_add(segment.graphemes(true).count());
+            if segment.chars().any(char::is_alphanumeric) {
+                ranges.push(start..end);
+            }
+            start = end;
+        }
+        ranges
+    }
+
+    fn matches_at(&self, query: &[&str], start: usize) -> bool {
+        self.fragments
+            .iter()
+            .skip(start)
+            .zip(query)
+            .all(|(fragment, grapheme)| fragment.grapheme == *grapheme)
     }
 
     pub fn get_visible_graphemes(&self, range: Range<usize>) -> String {
@@ -103,13 +247,19 @@ impl Line {
         let mut fragment_start = 0;
         for fragment in &self.fragments {
             let fragment_end = fragment.rendered_width.saturating_add(fragment_start);
-            if fragment_start > range.end {
+            if fragment_start >= range.end {
                 // Means starting from this fragment, it's out of the viewport.
                 // We don't need to add anything to the result string.
                 break;
             }
             if fragment_end > range.start {
-                if fragment_start < range.start || fragment_end > range.end {
+                if let GraphemeWidth::Tab(_) = fragment.rendered_width {
+                    // A tab is blank, so a clipped tab still shows its visible columns
+                    let visible_width = fragment_end
+                        .min(range.end)
+                        .saturating_sub(fragment_start.max(range.start));
+                    result.push_str(&" ".repeat(visible_width));
+                } else if fragment_start < range.start || fragment_end > range.end {
                     // Clip left or right
                     result.push('⋯');
                 } else if let Some(char) = fragment.replacement {
@@ -138,10 +288,6 @@ impl Line {
         self.fragments = Line::from(result.as_str()).fragments;
     }
 
-    pub fn append(&mut self, other: Self) {
-        self.fragments.extend(other.fragments);
-    }
-
     pub fn delete(&mut self, at: usize) {
         let mut result = String::new();
         self.fragments
@@ -154,20 +300,12 @@ impl Line {
             });
         self.fragments = Line::from(result.as_str()).fragments;
     }
-
-    pub fn split(&mut self, at: usize) -> Self {
-        if at > self.fragments.len() {
-            return Self::default();
-        }
-        let remainder = self.fragments.split_off(at);
-        Self {
-            fragments: remainder,
-        }
-    }
 }
 
-impl From<&str> for Line {
-    fn from(line_str: &str) -> Self {
+impl Line {
+    /// with_indentation splits the text into fragments, expanding each tab to the next tab stop.
+    pub fn with_indentation(line_str: &str, indentation: Indentation) -> Self {
+        let mut column: usize = 0;
         let fragments = line_str
             .graphemes(true)
             .map(|grapheme| {
@@ -181,7 +319,8 @@ impl From<&str> for Line {
                 match grapheme {
                     "\t" => {
                         replacement = Some(' ');
-                        rendered_width = GraphemeWidth::Half;
+                        rendered_width =
+                            GraphemeWidth::Tab(indentation.spaces_to_next_tab_stop(column));
                     }
                     _ if unicode_width > 0 && grapheme.trim().is_empty() => {
                         replacement = Some('␣');
@@ -189,11 +328,12 @@ impl From<&str> for Line {
                     }
                     _ if unicode_width == 0 => {
                         let mut chars = grapheme.chars();
-                        if let Some(ch) = chars.next() {
-                            if ch.is_control() && chars.next().is_none() {
-                                replacement = Some('▯');
-                                rendered_width = GraphemeWidth::Half;
-                            }
+                        if let Some(ch) = chars.next()
+                            && ch.is_control()
+                            && chars.next().is_none()
+                        {
+                            replacement = Some('▯');
+                            rendered_width = GraphemeWidth::Half;
                         }
                     }
                     _ => {}
@@ -218,6 +358,7 @@ impl From<&str> for Line {
                 //     }
                 // };
 
+                column = rendered_width.saturating_add(column);
                 TextFragment {
                     grapheme: grapheme.to_string(),
                     rendered_width,
@@ -229,6 +370,12 @@ impl From<&str> for Line {
     }
 }
 
+impl From<&str> for Line {
+    fn from(line_str: &str) -> Self {
+        Self::with_indentation(line_str, Indentation::default())
+    }
+}
+
 impl Display for Line {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let result = self
@@ -245,6 +392,8 @@ impl Display for Line {
 pub enum GraphemeWidth {
     Half,
     Full,
+    // A tab reaches up to the next tab stop
+    Tab(usize),
 }
 
 impl GraphemeWidth {
@@ -252,15 +401,17 @@ impl GraphemeWidth {
         match self {
             GraphemeWidth::Half => value.saturating_add(1),
             GraphemeWidth::Full => value.saturating_add(2),
+            GraphemeWidth::Tab(width) => value.saturating_add(width),
         }
     }
 }
 
-impl Into<u16> for GraphemeWidth {
-    fn into(self) -> u16 {
-        match self {
+impl From<GraphemeWidth> for u16 {
+    fn from(value: GraphemeWidth) -> Self {
+        match value {
             GraphemeWidth::Half => 1,
             GraphemeWidth::Full => 2,
+            GraphemeWidth::Tab(width) => u16::try_from(width).unwrap_or(u16::MAX),
         }
     }
 }
@@ -0,0 +1,26 @@
+use crate::{
+    editor::buffer::Buffer,
+    view::{Location, ScrollOffset},
+};
+
+/// Document is an open buffer together with where the caret and the view were when it was
+/// last shown.
+#[derive(Clone, Default)]
+pub struct Document {
+    pub buffer: Buffer,
+    pub location: Location,
+    pub scroll_offset: ScrollOffset,
+    // Documents that were never shown still have to be centered and checked for a swap file
+    pub visited: bool,
+}
+
+impl Document {
+    pub fn new(buffer: Buffer, location: Location) -> Self {
+        Self {
+            buffer,
+            location,
+            scroll_offset: ScrollOffset::default(),
+            visited: false,
+        }
+    }
+}
@@ -0,0 +1,38 @@
+/// LineNumbers is how the gutter numbers the lines.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum LineNumbers {
+    #[default]
+    Absolute,
+    // Distance from the caret line, which itself keeps its absolute number
+    Relative,
+}
+
+/// Gutter is the column left of the text that shows the line numbers.
+#[derive(Clone, Copy, Default)]
+pub struct Gutter {
+    pub line_numbers: LineNumbers,
+}
+
+impl Gutter {
+    /// width returns the columns needed for the largest line number, plus one column that
+    /// separates the numbers from the text.
+    pub fn width(line_count: usize) -> usize {
+        line_count.max(1).to_string().len().saturating_add(1)
+    }
+
+    /// render_number returns the right aligned number of the line, filling the given width.
+    pub fn render_number(
+        &self,
+        line_index: usize,
+        caret_line_index: usize,
+        width: usize,
+    ) -> String {
+        let number = match self.line_numbers {
+            LineNumbers::Relative if line_index != caret_line_index => {
+                line_index.abs_diff(caret_line_index)
+            }
+            _ => line_index.saturating_add(1),
+        };
+        format!("{number:>0$} ", width.saturating_sub(1))
+    }
+}
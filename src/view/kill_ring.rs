@@ -0,0 +1,35 @@
+use std::collections::VecDeque;
+
+/// How many cut or copied texts are kept for pasting
+const KILL_RING_SIZE: usize = 10;
+
+/// Clip is a cut or copied piece of text. The text may span several lines, which are
+/// separated by '\n'.
+#[derive(Clone)]
+pub struct Clip {
+    pub text: String,
+    // Cut or copied without a selection, so it is pasted above the caret line
+    pub is_whole_line: bool,
+}
+
+/// KillRing keeps the most recent clips, newest first, so that paste can cycle back through
+/// older ones.
+#[derive(Clone, Default)]
+pub struct KillRing {
+    clips: VecDeque<Clip>,
+}
+
+impl KillRing {
+    pub fn push(&mut self, clip: Clip) {
+        if self.clips.len() >= KILL_RING_SIZE {
+            self.clips.pop_back();
+        }
+        self.clips.push_front(clip);
+    }
+
+    /// get returns the clip that is `age` clips older than the newest one, wrapping around to
+    /// the newest after the oldest.
+    pub fn get(&self, age: usize) -> Option<&Clip> {
+        self.clips.get(age.checked_rem(self.clips.len())?)
+    }
+}
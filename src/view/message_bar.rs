@@ -0,0 +1,70 @@
+use std::time::{Duration, Instant};
+use unicode_width::UnicodeWidthStr;
+
+/// How long a message stays in the message bar
+const MESSAGE_DURATION: Duration = Duration::from_secs(5);
+
+#[derive(Clone)]
+struct Message {
+    text: String,
+    shown_at: Instant,
+}
+
+/// MessageBar is the bottom row of the screen. It shows the open prompt, if any, and
+/// notifications that disappear on their own after a few seconds.
+#[derive(Clone, Default)]
+pub struct MessageBar {
+    message: Option<Message>,
+}
+
+impl MessageBar {
+    pub fn set(&mut self, text: &str) {
+        self.message = Some(Message {
+            text: text.to_string(),
+            shown_at: Instant::now(),
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.message = None;
+    }
+
+    /// clear_expired drops the message once it has been shown long enough and tells whether
+    /// the bar needs to be redrawn.
+    pub fn clear_expired(&mut self) -> bool {
+        if self
+            .message
+            .as_ref()
+            .is_some_and(|message| message.shown_at.elapsed() >= MESSAGE_DURATION)
+        {
+            self.message = None;
+            return true;
+        }
+        false
+    }
+
+    /// render_text returns the bar for the given width. The prompt keeps the left side, and
+    /// the message is only shown to its right when both fit.
+    pub fn render_text(&self, prompt_text: Option<String>, width: usize) -> String {
+        let message = self.message.as_ref().map(|message| message.text.as_str());
+        match (prompt_text, message) {
+            (Some(prompt_text), Some(message)) => {
+                let used = prompt_text.width().saturating_add(message.width());
+                // Keep at least two spaces between the prompt and the message
+                match width.checked_sub(used).filter(|padding| *padding >= 2) {
+                    Some(padding) => format!("{prompt_text}{}{message}", " ".repeat(padding)),
+                    None => prompt_text,
+                }
+            }
+            (Some(prompt_text), None) => prompt_text,
+            (None, Some(message)) => message
+                .chars()
+                .scan(0, |used, c| {
+                    *used = c.to_string().width().saturating_add(*used);
+                    (*used <= width).then_some(c)
+                })
+                .collect(),
+            (None, None) => String::new(),
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use crossterm::Command;
+use std::fmt;
+
+/// Largest encoded clipboard payload sent by default. Terminals drop longer sequences, and
+/// some of them silently.
+const DEFAULT_MAX_BYTES: usize = 100_000;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Osc52 configures copying to the system clipboard through the host terminal, which also
+/// works over SSH and inside tmux.
+#[derive(Clone, Copy, Debug)]
+pub struct Osc52 {
+    pub enabled: bool,
+    // Limit for the base64 encoded text, beyond which the text only stays inside the editor
+    pub max_bytes: usize,
+}
+
+impl Default for Osc52 {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_bytes: DEFAULT_MAX_BYTES,
+        }
+    }
+}
+
+/// SetClipboard asks the terminal to put the base64 encoded text on the system clipboard.
+pub struct SetClipboard(pub String);
+
+impl SetClipboard {
+    pub fn new(text: &str) -> Self {
+        Self(base64_encode(text.as_bytes()))
+    }
+}
+
+impl Command for SetClipboard {
+    fn write_ansi(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        write!(f, "\x1b]52;c;{}\x07", self.0)
+    }
+
+    #[cfg(windows)]
+    fn execute_winapi(&self) -> std::io::Result<()> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "OSC 52 needs a terminal that understands ANSI sequences",
+        ))
+    }
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3).saturating_mul(4));
+    for chunk in bytes.chunks(3) {
+        let byte_at = |index: usize| u32::from(chunk.get(index).copied().unwrap_or(0));
+        let group = (byte_at(0) << 16) | (byte_at(1) << 8) | byte_at(2);
+        // Three bytes make four characters, and missing bytes are padded with '='
+        for index in 0..4 {
+            if index <= chunk.len() {
+                let sextet = (group >> (18 - 6 * index)) & 0x3f;
+                encoded.push(char::from(BASE64_ALPHABET[sextet as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_encode_empty() {
+        assert_eq!(base64_encode(b""), "");
+    }
+
+    #[test]
+    fn base64_encode_pads_partial_groups() {
+        // Test vectors from RFC 4648, covering one and two missing bytes
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        assert_eq!(base64_encode(b"foob"), "Zm9vYg==");
+        assert_eq!(base64_encode(b"fooba"), "Zm9vYmE=");
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn base64_encode_multibyte_utf8() {
+        assert_eq!(base64_encode("é".as_bytes()), "w6k=");
+        assert_eq!(base64_encode("日本".as_bytes()), "5pel5pys");
+        assert_eq!(base64_encode("€ 1".as_bytes()), "4oKsIDE=");
+    }
+
+    #[test]
+    fn base64_encode_high_bits() {
+        assert_eq!(base64_encode(&[0x00, 0xff, 0x80]), "AP+A");
+        assert_eq!(base64_encode(&[0xfb, 0xff]), "+/8=");
+    }
+
+    #[test]
+    fn set_clipboard_writes_osc52_sequence() {
+        let mut ansi = String::new();
+        SetClipboard::new("hi").write_ansi(&mut ansi).unwrap();
+        assert_eq!(ansi, "\x1b]52;c;aGk=\x07");
+    }
+}
@@ -0,0 +1,128 @@
+use crate::view::{
+    Line,
+    terminal_command::{Direction, SpecialKey, TerminalCommand},
+};
+use unicode_width::UnicodeWidthStr;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PromptKind {
+    SaveAs,
+    RecoverSwap,
+    Search,
+    ReplacePattern,
+    ReplaceWith,
+    ReplaceConfirm,
+    GoToLine,
+    Open,
+}
+
+pub enum PromptEvent {
+    Accepted(String),
+    Cancelled,
+    Edited,
+}
+
+/// Prompt is a single line input rendered at the bottom of the screen.
+/// The caret is measured in graphemes of the entered value.
+#[derive(Clone)]
+pub struct Prompt {
+    pub kind: PromptKind,
+    label: String,
+    value: Line,
+    caret: usize,
+}
+
+impl Prompt {
+    pub fn new(kind: PromptKind, label: &str, initial_value: &str) -> Self {
+        let value = Line::from(initial_value);
+        let caret = value.grapheme_count();
+        Self {
+            kind,
+            label: label.to_string(),
+            value,
+            caret,
+        }
+    }
+
+    pub fn value(&self) -> String {
+        self.value.to_string()
+    }
+
+    pub fn handle_command(&mut self, command: TerminalCommand) -> PromptEvent {
+        match command {
+            TerminalCommand::OrdinaryChar(key_code) => {
+                if let Some(c) = key_code.as_char() {
+                    let old_len = self.value.grapheme_count();
+                    self.value.insert_char(c, self.caret);
+                    let new_len = self.value.grapheme_count();
+                    self.caret = self.caret.saturating_add(new_len.saturating_sub(old_len));
+                }
+            }
+            TerminalCommand::PasteText(text) => {
+                // A prompt holds a single line, so only the first pasted line is used
+                for c in text.lines().next().unwrap_or_default().chars() {
+                    let old_len = self.value.grapheme_count();
+                    self.value.insert_char(c, self.caret);
+                    let new_len = self.value.grapheme_count();
+                    self.caret = self.caret.saturating_add(new_len.saturating_sub(old_len));
+                }
+            }
+            TerminalCommand::SpecialKey(SpecialKey::Enter) => {
+                return PromptEvent::Accepted(self.value.to_string());
+            }
+            TerminalCommand::SpecialKey(SpecialKey::Escape) => return PromptEvent::Cancelled,
+            TerminalCommand::SpecialKey(SpecialKey::Backspace) if self.caret > 0 => {
+                self.caret = self.caret.saturating_sub(1);
+                self.value.delete(self.caret);
+            }
+            TerminalCommand::SpecialKey(SpecialKey::Delete) => {
+                self.value.delete(self.caret);
+            }
+            TerminalCommand::MoveCaret(Direction::Left) => {
+                self.caret = self.caret.saturating_sub(1);
+            }
+            TerminalCommand::MoveCaret(Direction::Right) => {
+                self.caret = self
+                    .caret
+                    .saturating_add(1)
+                    .min(self.value.grapheme_count());
+            }
+            TerminalCommand::MoveCaret(Direction::Home) => {
+                self.caret = 0;
+            }
+            TerminalCommand::MoveCaret(Direction::End) => {
+                self.caret = self.value.grapheme_count();
+            }
+            _ => {}
+        }
+        PromptEvent::Edited
+    }
+
+    /// render_text returns the label followed by the part of the value that fits into the
+    /// given width, scrolled so that the caret stays visible.
+    pub fn render_text(&self, width: usize) -> String {
+        let available = width.saturating_sub(self.label.width());
+        let left = self.scroll_offset(available);
+        let right = left
+            .saturating_add(available)
+            .min(self.value.graphemes_width());
+        let visible = self.value.get_visible_graphemes(left..right);
+        format!("{}{visible}", self.label)
+    }
+
+    /// caret_x returns the screen column of the caret within the prompt line.
+    pub fn caret_x(&self, width: usize) -> usize {
+        let available = width.saturating_sub(self.label.width());
+        let caret_width = self.value.column_of(self.caret);
+        self.label
+            .width()
+            .saturating_add(caret_width.saturating_sub(self.scroll_offset(available)))
+    }
+
+    fn scroll_offset(&self, available: usize) -> usize {
+        self.value
+            .column_of(self.caret)
+            .saturating_add(1)
+            .saturating_sub(available)
+    }
+}
@@ -0,0 +1,98 @@
+use crate::{
+    editor::buffer::{Buffer, RegexMatch},
+    view::Location,
+};
+use regex::Regex;
+
+/// Replace walks through the matches of a regular expression, starting at the caret and
+/// wrapping around the end of the buffer once, until it is back where it started.
+#[derive(Clone)]
+pub struct Replace {
+    regex: Regex,
+    template: String,
+    next_from: Location,
+    stop_at: Location,
+    wrapped: bool,
+    pub current: Option<RegexMatch>,
+    pub count: usize,
+}
+
+impl Replace {
+    pub fn new(regex: Regex, from: Location) -> Self {
+        Self {
+            regex,
+            template: String::new(),
+            next_from: from,
+            stop_at: from,
+            wrapped: false,
+            current: None,
+            count: 0,
+        }
+    }
+
+    /// set_template sets the replacement, in which `$1` or `${name}` refer to capture groups.
+    pub fn set_template(&mut self, template: String) {
+        self.template = template;
+    }
+
+    /// advance finds the next match and makes it the current one.
+    pub fn advance(&mut self, buffer: &Buffer) -> Option<&RegexMatch> {
+        self.current = None;
+        loop {
+            match buffer.find_regex(&self.regex, &self.template, self.next_from) {
+                Some(found) if self.wrapped && found.start >= self.stop_at => return None,
+                Some(found) => {
+                    self.current = Some(found);
+                    return self.current.as_ref();
+                }
+                None if !self.wrapped => {
+                    self.wrapped = true;
+                    self.next_from = Location::default();
+                }
+                None => return None,
+            }
+        }
+    }
+
+    /// skip continues the search after the current match without replacing it.
+    pub fn skip(&mut self) {
+        if let Some(current) = &self.current {
+            self.next_from = Self::after(current, current.end);
+        }
+    }
+
+    /// replace_current replaces the current match in the buffer and returns the location
+    /// right after the replacement.
+    pub fn replace_current(&mut self, buffer: &mut Buffer) -> Option<Location> {
+        let current = self.current.take()?;
+        let caret_after = buffer.replace_range(current.start, current.end, &current.replacement);
+        self.count = self.count.saturating_add(1);
+        self.next_from = Self::after(&current, caret_after);
+
+        // Matches never span lines, so only the rest of the same line moves
+        if self.wrapped
+            && current.end.line_index == self.stop_at.line_index
+            && current.end <= self.stop_at
+        {
+            self.stop_at.grapheme_index = self
+                .stop_at
+                .grapheme_index
+                .saturating_sub(current.end.grapheme_index)
+                .saturating_add(caret_after.grapheme_index);
+        }
+        Some(caret_after)
+    }
+
+    /// after returns where to continue searching once the match has been dealt with. After an
+    /// empty match it moves on by one grapheme, or the match would be found again right there.
+    fn after(current: &RegexMatch, location: Location) -> Location {
+        if current.end > current.start {
+            location
+        } else {
+            Location {
+                grapheme_index: location.grapheme_index.saturating_add(1),
+                ..location
+            }
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use crate::{editor::file_type::FileType, view::Location};
+use unicode_width::UnicodeWidthStr;
+
+/// DocumentStatus is what the status bar shows about the buffer and the caret.
+pub struct DocumentStatus {
+    pub file_name: String,
+    // 1-based position of the buffer in the buffer list
+    pub document_number: usize,
+    pub document_count: usize,
+    pub is_modified: bool,
+    pub location: Location,
+    pub line_count: usize,
+    pub file_type: FileType,
+}
+
+impl DocumentStatus {
+    /// render_text lays the status out over the full width, with the file on the left and the
+    /// caret position on the right. Line and column are shown 1-based.
+    pub fn render_text(&self, width: usize) -> String {
+        let modified = if self.is_modified { " (modified)" } else { "" };
+        let mut left = format!(" {}{modified}", self.file_name);
+        if self.document_count > 1 {
+            left = format!(" [{}/{}]{left}", self.document_number, self.document_count);
+        }
+        let right = format!(
+            "{} | Ln {}/{}, Col {} ",
+            self.file_type,
+            self.location.line_index.saturating_add(1),
+            self.line_count,
+            self.location.grapheme_index.saturating_add(1),
+        );
+
+        let padding = width
+            .saturating_sub(left.width().saturating_add(right.width()))
+            .max(1);
+        let mut status = format!("{left}{}{right}", " ".repeat(padding));
+        if status.width() > width {
+            // Not enough room for both sides, so keep what fits from the left
+            status = status
+                .chars()
+                .scan(0, |used, c| {
+                    *used = c.to_string().width().saturating_add(*used);
+                    (*used <= width).then_some(c)
+                })
+                .collect();
+        }
+        status
+    }
+}
@@ -1,35 +1,107 @@
 use crate::{
-    editor::{buffer::Buffer, editor_command::EditorCommand},
+    editor::{
+        buffer::{Buffer, SwapState},
+        editor_command::EditorCommand,
+        line_ending::LineEnding,
+    },
     view::{
-        Line, Location, Position, Size, TextFragment, View,
+        Line, Location, Position, ScrollOffset, Size, View,
+        document::Document,
+        gutter::{Gutter, LineNumbers},
+        kill_ring::{Clip, KillRing},
+        message_bar::MessageBar,
+        osc52::{Osc52, SetClipboard},
+        prompt::{Prompt, PromptEvent, PromptKind},
+        replace::Replace,
+        status_bar::DocumentStatus,
         terminal_command::{Direction, SpecialKey, TerminalCommand},
     },
 };
 use crossterm::{
     Command,
     cursor::{self},
-    event::{Event, KeyEvent, KeyEventKind, read},
+    event::{
+        self, Event, KeyCode, KeyEvent, KeyEventKind, KeyboardEnhancementFlags, MouseEvent,
+        MouseEventKind, poll, read,
+    },
     queue, style,
     terminal::{self, Clear, enable_raw_mode},
 };
-use std::io::{self, Write, stdout};
+use regex::Regex;
+use std::{
+    cmp::Ordering,
+    io::{self, Write, stdout},
+    mem,
+    ops::Range,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
 const NAME: &str = env!("CARGO_PKG_NAME");
 const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// How long evaluate_keypress waits for an event before returning to run periodic work
+const POLL_TIMEOUT: Duration = Duration::from_millis(500);
+/// Minimum time between two writes of the swap file
+const SWAP_INTERVAL: Duration = Duration::from_secs(2);
+/// Longest pause between the clicks of a double or triple click
+const MULTI_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+/// Shown while the user tries to get past the question about recovering a swap file
+const RECOVERY_HINT: &str = "Type y to recover the changes or n to discard them.";
+/// Lines scrolled by one step of the mouse wheel
+const SCROLL_LINES: usize = 3;
 
 #[derive(Default, Clone)]
 pub struct Terminal {
+    // The buffer being edited, taken out of the document list while it is shown
     buffer: Buffer,
+    documents: Vec<Document>,
+    current_document: usize,
     needs_render: bool,
     size: Size,
     location: Location,
-    scroll_offset: Location,
+    // Screen column that vertical movement returns to after passing through shorter lines
+    desired_column: Option<usize>,
+    scroll_offset: ScrollOffset,
+    prompt: Option<Prompt>,
+    message_bar: MessageBar,
+    quit_pending: bool,
+    // Whether the terminal tells apart keys like Enter and Shift-Enter, which is undone on exit
+    keyboard_enhanced: bool,
+    // Revision of the buffer that the swap file holds
+    swapped_revision: usize,
+    swap_written_at: Option<Instant>,
+    // Caret location and scroll offset to return to when a search is cancelled
+    search_origin: Option<(Location, ScrollOffset)>,
+    replace: Option<Replace>,
+    // Text shown in reverse video, such as the current search match
+    highlight: Option<(Location, Location)>,
+    // The other end of the selection, which runs from here to the caret
+    selection_anchor: Option<Location>,
+    kill_ring: KillRing,
+    osc52: Osc52,
+    // Time, location and count of the last click, to recognize double and triple clicks
+    last_click: Option<(Instant, Location, usize)>,
+    // Where the last paste put its text and how old the pasted clip was, for CyclePaste
+    last_paste: Option<(Location, Location, usize)>,
+    // Last status bar drawn, so that it is only redrawn when it changes
+    rendered_status: String,
+    gutter: Gutter,
+    // Caret line the relative line numbers were last drawn for
+    rendered_caret_line: usize,
 }
 
 impl Terminal {
-    pub fn new(file_content: String) -> Self {
-        let terminal = Terminal {
-            buffer: Buffer::new(file_content),
+    pub fn new(mut documents: Vec<Document>, osc52: Osc52) -> Self {
+        if documents.is_empty() {
+            documents.push(Document::new(
+                Buffer::new(String::new()),
+                Location::default(),
+            ));
+        }
+        let mut terminal = Terminal {
+            buffer: Buffer::new(String::new()),
+            documents,
+            current_document: 0,
             needs_render: true,
             size: Size {
                 width: terminal::size().unwrap_or_default().0 as usize,
@@ -39,33 +111,224 @@ impl Terminal {
                 grapheme_index: 0,
                 line_index: 0,
             },
-            scroll_offset: Location {
-                grapheme_index: 0,
+            desired_column: None,
+            scroll_offset: ScrollOffset {
                 line_index: 0,
+                column: 0,
             },
+            prompt: None,
+            message_bar: MessageBar::default(),
+            quit_pending: false,
+            keyboard_enhanced: false,
+            swapped_revision: 0,
+            swap_written_at: None,
+            search_origin: None,
+            replace: None,
+            highlight: None,
+            selection_anchor: None,
+            kill_ring: KillRing::default(),
+            osc52,
+            last_paste: None,
+            last_click: None,
+            rendered_status: String::new(),
+            gutter: Gutter::default(),
+            rendered_caret_line: 0,
         };
 
         match enable_raw_mode() {
             Ok(_) => {}
             Err(e) => {
-                eprintln!("Error enabling raw mode: {}", e);
+                terminal.set_message(&format!("Error enabling raw mode: {e}"));
             }
         };
+        // Without the enhancement, Shift-Enter and Enter send the same bytes
+        terminal.keyboard_enhanced = terminal::supports_keyboard_enhancement().unwrap_or(false);
         match terminal.enter_alternate_screen() {
             Ok(_) => {}
             Err(e) => {
-                eprintln!("Error entering alternate screen: {}", e);
+                terminal.set_message(&format!("Error entering alternate screen: {e}"));
             }
         };
         match terminal.clear_screen() {
             Ok(_) => {}
             Err(e) => {
-                eprintln!("Error clearing screen: {}", e);
+                terminal.set_message(&format!("Error clearing screen: {e}"));
             }
         };
+        terminal.load_document(0);
+        terminal.report_recoverable_documents();
         terminal
     }
 
+    /// report_recoverable_documents tells about swap files left by a crash for documents that
+    /// are not shown yet. Recovery is offered once they are switched to.
+    fn report_recoverable_documents(&mut self) {
+        let count = self
+            .documents
+            .iter()
+            .filter(|document| {
+                !document.visited
+                    && matches!(document.buffer.swap_state(), SwapState::Recoverable { .. })
+            })
+            .count();
+        match count {
+            0 => {}
+            1 => self.set_message("Another open file has unsaved changes from a crash."),
+            _ => self.set_message(&format!(
+                "{count} other open files have unsaved changes from a crash."
+            )),
+        }
+    }
+
+    /// load_document makes the document with the given index the one being edited. Its place
+    /// in the list holds an empty document until store_document puts it back.
+    fn load_document(&mut self, index: usize) {
+        let Some(document) = self.documents.get_mut(index) else {
+            return;
+        };
+        let document = mem::take(document);
+        self.current_document = index;
+        self.buffer = document.buffer;
+        self.location = document.location;
+        self.scroll_offset = document.scroll_offset;
+        self.swapped_revision = self.buffer.revision();
+        if !document.visited {
+            self.go_to(document.location);
+            // Saving creates the file, but a mistyped name should not go unnoticed
+            if let Some(file_path) = &self.buffer.file_path
+                && !file_path.exists()
+            {
+                self.set_message(&format!("New file: {}", file_path.display()));
+            }
+            if self.buffer.line_ending == LineEnding::Mixed {
+                self.set_message("Mixed line endings, kept as they are.");
+            }
+            match self.buffer.swap_state() {
+                SwapState::None => {}
+                SwapState::Recoverable { .. } => self.open_prompt(PromptKind::RecoverSwap),
+                SwapState::InUse { pid } => {
+                    self.set_message(&format!("The file is also being edited by process {pid}."))
+                }
+            }
+        }
+        self.needs_render = true;
+    }
+
+    /// store_document puts the buffer being edited back into the document list.
+    fn store_document(&mut self) {
+        // The swap file is only kept up to date for the buffer being edited
+        if self.buffer.is_modified
+            && self.edited_since_swap()
+            && let Err(err) = self.buffer.write_swap()
+        {
+            self.set_message(&format!("Could not write swap file: {err}"));
+        }
+        self.swap_written_at = None;
+        let document = Document {
+            buffer: mem::take(&mut self.buffer),
+            location: self.location,
+            scroll_offset: self.scroll_offset,
+            visited: true,
+        };
+        if let Some(slot) = self.documents.get_mut(self.current_document) {
+            *slot = document;
+        }
+    }
+
+    fn switch_document(&mut self, index: usize) {
+        if index == self.current_document || index >= self.documents.len() {
+            return;
+        }
+        self.store_document();
+        self.highlight = None;
+        // Messages about the document itself, e.g. that it is a new file, take precedence
+        let name = self
+            .documents
+            .get(index)
+            .map(|document| document.buffer.display_name())
+            .unwrap_or_default();
+        self.set_message(&format!("Switched to {name}."));
+        self.load_document(index);
+    }
+
+    fn cycle_documents(&mut self, forward: bool) {
+        let count = self.documents.len();
+        if count < 2 {
+            self.set_message("No other buffers open.");
+            return;
+        }
+        let index = if forward {
+            self.current_document.saturating_add(1) % count
+        } else {
+            self.current_document
+                .saturating_add(count)
+                .saturating_sub(1)
+                % count
+        };
+        self.switch_document(index);
+    }
+
+    /// buffers returns every open buffer in list order, including the one being edited.
+    fn buffers(&self) -> impl Iterator<Item = &Buffer> {
+        self.documents.iter().enumerate().map(|(index, document)| {
+            if index == self.current_document {
+                &self.buffer
+            } else {
+                &document.buffer
+            }
+        })
+    }
+
+    fn list_documents(&mut self) {
+        let list = self
+            .buffers()
+            .enumerate()
+            .map(|(index, buffer)| {
+                let modified = if buffer.is_modified { "*" } else { "" };
+                let entry = format!(
+                    "{} {}{modified}",
+                    index.saturating_add(1),
+                    buffer.display_name()
+                );
+                if index == self.current_document {
+                    format!("[{entry}]")
+                } else {
+                    entry
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("  ");
+        self.set_message(&list);
+    }
+
+    /// open_file switches to the file if it is already open, and otherwise adds it to the
+    /// buffer list. A file that does not exist yet is created on save.
+    fn open_file(&mut self, path: &str) {
+        let open_index = self
+            .buffers()
+            .position(|buffer| buffer.is_bound_to(Path::new(path)));
+        if let Some(index) = open_index {
+            self.switch_document(index);
+            return;
+        }
+        let mut buffer = match Buffer::load(path) {
+            Ok(buffer) => buffer,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                let mut buffer = Buffer::new(String::new());
+                buffer.file_path = Some(path.into());
+                buffer
+            }
+            Err(err) => {
+                self.set_message(&format!("Could not open {path}: {err}"));
+                return;
+            }
+        };
+        buffer.indentation = self.buffer.indentation;
+        self.documents
+            .push(Document::new(buffer, Location::default()));
+        self.switch_document(self.documents.len().saturating_sub(1));
+    }
+
     fn queue_command<T: Command>(&self, command: T) -> io::Result<&Self> {
         let mut stdout = io::stdout();
         queue!(stdout, command)?;
@@ -83,6 +346,747 @@ impl Terminal {
         Ok(welcome_message)
     }
 
+    /// highlight_in_line returns the rendered columns of the line that are highlighted.
+    fn highlight_in_line(&self, line_index: usize, line: &Line) -> Option<Range<usize>> {
+        let (start, end) = self.selection().or(self.highlight)?;
+        if line_index < start.line_index || line_index > end.line_index {
+            return None;
+        }
+        let start_col = if line_index == start.line_index {
+            line.column_of(start.grapheme_index)
+        } else {
+            0
+        };
+        let end_col = if line_index == end.line_index {
+            line.column_of(end.grapheme_index)
+        } else {
+            line.graphemes_width()
+        };
+        Some(start_col..end_col)
+    }
+
+    /// print_line prints the visible columns of the line, with the highlighted columns in
+    /// reverse video.
+    fn print_line(
+        &self,
+        line: &Line,
+        visible: Range<usize>,
+        highlight: Option<Range<usize>>,
+    ) -> io::Result<()> {
+        let Some(highlight) = highlight else {
+            return self.print(line.get_visible_graphemes(visible).as_str());
+        };
+        let start = highlight.start.clamp(visible.start, visible.end);
+        let end = highlight.end.clamp(start, visible.end);
+        self.print(line.get_visible_graphemes(visible.start..start).as_str())?;
+        self.queue_command(style::SetAttribute(style::Attribute::Reverse))?;
+        self.print(line.get_visible_graphemes(start..end).as_str())?;
+        self.queue_command(style::SetAttribute(style::Attribute::NoReverse))?;
+        self.print(line.get_visible_graphemes(end..visible.end).as_str())
+    }
+
+    /// text_area_size returns the part of the screen used for the buffer, which excludes the
+    /// status bar and the message bar below it.
+    fn text_area_size(&self) -> Size {
+        let size = self.size().unwrap_or_default();
+        Size {
+            width: size.width,
+            height: size.height.saturating_sub(2),
+        }
+    }
+
+    /// gutter_width returns the columns taken by the line numbers. The gutter is left out when
+    /// it would take up half of the screen or more.
+    fn gutter_width(&self) -> usize {
+        let gutter_width = Gutter::width(self.buffer.line_count());
+        if gutter_width.saturating_mul(2) > self.text_area_size().width {
+            0
+        } else {
+            gutter_width
+        }
+    }
+
+    fn toggle_relative_line_numbers(&mut self) {
+        self.gutter.line_numbers = match self.gutter.line_numbers {
+            LineNumbers::Absolute => {
+                self.set_message("Relative line numbers on.");
+                LineNumbers::Relative
+            }
+            LineNumbers::Relative => {
+                self.set_message("Relative line numbers off.");
+                LineNumbers::Absolute
+            }
+        };
+        self.needs_render = true;
+    }
+
+    fn document_status(&self) -> DocumentStatus {
+        DocumentStatus {
+            file_name: self.buffer.display_name(),
+            document_number: self.current_document.saturating_add(1),
+            document_count: self.documents.len(),
+            is_modified: self.buffer.is_modified,
+            location: self.location,
+            line_count: self.buffer.line_count(),
+            file_type: self.buffer.file_type(),
+        }
+    }
+
+    /// set_message shows a message in the message bar for a few seconds.
+    fn set_message(&mut self, message: &str) {
+        self.message_bar.set(message);
+        self.needs_render = true;
+    }
+
+    fn open_prompt(&mut self, kind: PromptKind) {
+        let prompt = match kind {
+            PromptKind::SaveAs => {
+                let file_path = self
+                    .buffer
+                    .file_path
+                    .as_ref()
+                    .map(|path| path.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                Prompt::new(kind, "Save as: ", &file_path)
+            }
+            PromptKind::RecoverSwap => match self.buffer.swap_state() {
+                SwapState::Recoverable {
+                    is_older_than_file: true,
+                } => Prompt::new(
+                    kind,
+                    "Found unsaved changes older than the file. Recover? (y/n) ",
+                    "",
+                ),
+                _ => Prompt::new(
+                    kind,
+                    "Found unsaved changes from a crash. Recover? (y/n) ",
+                    "",
+                ),
+            },
+            PromptKind::Search => {
+                self.search_origin = Some((self.location, self.scroll_offset));
+                Prompt::new(kind, "Search (Up/Down: previous/next, Ctrl-F: done): ", "")
+            }
+            PromptKind::ReplacePattern => Prompt::new(kind, "Replace (regex): ", ""),
+            PromptKind::ReplaceWith => Prompt::new(kind, "Replace with ($1 for groups): ", ""),
+            PromptKind::ReplaceConfirm => {
+                Prompt::new(kind, "Replace this match? (y)es (n)o (a)ll (q)uit", "")
+            }
+            PromptKind::GoToLine => Prompt::new(kind, "Go to line (line or line:column): ", ""),
+            PromptKind::Open => Prompt::new(kind, "Open: ", ""),
+        };
+        self.prompt = Some(prompt);
+        self.scroll_location_into_view();
+        self.needs_render = true;
+    }
+
+    fn handle_prompt_command(&mut self, command: TerminalCommand) -> io::Result<()> {
+        let Some(prompt) = self.prompt.as_mut() else {
+            return Ok(());
+        };
+        let kind = prompt.kind;
+        if kind == PromptKind::Search {
+            let query = prompt.value();
+            match command {
+                TerminalCommand::MoveCaret(Direction::Down)
+                | TerminalCommand::SpecialKey(SpecialKey::Enter) => {
+                    // Skip the match under the caret
+                    let from = Location {
+                        grapheme_index: self.location.grapheme_index.saturating_add(1),
+                        ..self.location
+                    };
+                    self.search_next(&query, from);
+                    return Ok(());
+                }
+                TerminalCommand::MoveCaret(Direction::Up)
+                | TerminalCommand::SpecialKey(SpecialKey::ShiftEnter) => {
+                    self.search_previous(&query);
+                    return Ok(());
+                }
+                TerminalCommand::Search => {
+                    self.prompt = None;
+                    self.search_origin = None;
+                    self.highlight = None;
+                    self.needs_render = true;
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+        if kind == PromptKind::ReplaceConfirm {
+            self.handle_replace_confirmation(command);
+            return Ok(());
+        }
+        match prompt.handle_command(command) {
+            PromptEvent::Edited if kind == PromptKind::Search => {
+                let query = self.prompt.as_ref().map(Prompt::value).unwrap_or_default();
+                if let Some((origin, _)) = self.search_origin {
+                    self.search_next(&query, origin);
+                }
+            }
+            PromptEvent::Edited => {}
+            // The swap file must not be overwritten before the user has decided about it
+            PromptEvent::Cancelled if kind == PromptKind::RecoverSwap => {
+                self.set_message(RECOVERY_HINT);
+            }
+            PromptEvent::Cancelled => {
+                self.prompt = None;
+                self.replace = None;
+                self.highlight = None;
+                if let Some((location, scroll_offset)) = self.search_origin.take() {
+                    self.location = location;
+                    self.scroll_offset = scroll_offset;
+                }
+            }
+            PromptEvent::Accepted(value) => {
+                self.prompt = None;
+                match kind {
+                    PromptKind::SaveAs if !value.is_empty() => {
+                        let result = self.buffer.save_as(&value);
+                        let failed = result.is_err();
+                        self.report_save(result);
+                        // Ask again with the rejected path, so that it can be corrected
+                        if failed {
+                            self.prompt = Some(Prompt::new(kind, "Save as: ", &value));
+                        }
+                    }
+                    PromptKind::SaveAs => self.set_message("Save aborted."),
+                    PromptKind::RecoverSwap if value.trim().eq_ignore_ascii_case("y") => {
+                        let result = self.buffer.recover_swap();
+                        match result {
+                            Ok(_) => self.set_message("Unsaved changes recovered."),
+                            Err(err) => self.set_message(&format!("Could not recover: {err}")),
+                        }
+                    }
+                    PromptKind::RecoverSwap if value.trim().eq_ignore_ascii_case("n") => {
+                        let _ = self.buffer.discard_swap();
+                    }
+                    // Only an explicit answer may throw the changes away
+                    PromptKind::RecoverSwap => self.open_prompt(PromptKind::RecoverSwap),
+                    PromptKind::Search | PromptKind::ReplaceConfirm => {}
+                    PromptKind::ReplacePattern => match Regex::new(&value) {
+                        Ok(regex) => {
+                            self.replace = Some(Replace::new(regex, self.location));
+                            self.open_prompt(PromptKind::ReplaceWith);
+                        }
+                        Err(err) => self.set_message(&format!("Invalid regex: {err}")),
+                    },
+                    PromptKind::ReplaceWith => {
+                        if let Some(replace) = self.replace.as_mut() {
+                            replace.set_template(value);
+                        }
+                        self.show_next_replacement();
+                    }
+                    PromptKind::Open if value.trim().is_empty() => {}
+                    PromptKind::Open => self.open_file(&value),
+                    PromptKind::GoToLine if value.trim().is_empty() => {}
+                    PromptKind::GoToLine => match Location::parse_line_column(&value) {
+                        Some(location) => self.go_to(location),
+                        None => self.set_message(&format!("Invalid line number: {value}")),
+                    },
+                }
+            }
+        }
+        self.needs_render = true;
+        Ok(())
+    }
+
+    /// search_next moves the caret to the first match at or after `from`. An empty query moves
+    /// the caret back to where the search started.
+    fn search_next(&mut self, query: &str, from: Location) {
+        if query.is_empty() {
+            self.highlight = None;
+            if let Some((origin, _)) = self.search_origin {
+                self.location = origin;
+            }
+        } else if let Some(location) = self.buffer.find_next(query, from) {
+            if location < from {
+                self.set_message("Search wrapped.");
+            }
+            self.location = location;
+            self.highlight_match(query);
+        }
+        self.scroll_location_into_view();
+        self.needs_render = true;
+    }
+
+    /// highlight_match highlights the query at the caret.
+    fn highlight_match(&mut self, query: &str) {
+        let end = Location {
+            grapheme_index: self
+                .location
+                .grapheme_index
+                .saturating_add(Line::from(query).grapheme_count()),
+            ..self.location
+        };
+        self.highlight = Some((self.location, end));
+    }
+
+    fn search_previous(&mut self, query: &str) {
+        if let Some(location) = self.buffer.find_previous(query, self.location) {
+            if location > self.location {
+                self.set_message("Search wrapped.");
+            }
+            self.location = location;
+            self.highlight_match(query);
+        }
+        self.scroll_location_into_view();
+        self.needs_render = true;
+    }
+
+    /// handle_replace_confirmation answers the question whether to replace the current match.
+    fn handle_replace_confirmation(&mut self, command: TerminalCommand) {
+        let Some(replace) = self.replace.as_mut() else {
+            return;
+        };
+        match command {
+            TerminalCommand::OrdinaryChar(KeyCode::Char('y')) => {
+                if let Some(location) = replace.replace_current(&mut self.buffer) {
+                    self.location = location;
+                }
+            }
+            TerminalCommand::OrdinaryChar(KeyCode::Char('n')) => replace.skip(),
+            TerminalCommand::OrdinaryChar(KeyCode::Char('a')) => {
+                // Replacing everything is undone in one go
+                self.buffer.history.start_group();
+                while let Some(location) = replace.replace_current(&mut self.buffer) {
+                    self.location = location;
+                    replace.advance(&self.buffer);
+                }
+                self.buffer.history.end_group();
+            }
+            TerminalCommand::OrdinaryChar(KeyCode::Char('q'))
+            | TerminalCommand::SpecialKey(SpecialKey::Escape) => {
+                replace.current = None;
+                self.finish_replace();
+                return;
+            }
+            _ => return,
+        }
+        self.show_next_replacement();
+    }
+
+    /// show_next_replacement moves the caret to the next match and asks whether to replace it,
+    /// or reports how many matches were replaced once there are no more.
+    fn show_next_replacement(&mut self) {
+        let Some(replace) = self.replace.as_mut() else {
+            return;
+        };
+        match replace.advance(&self.buffer) {
+            Some(found) => {
+                self.location = found.start;
+                self.highlight = Some((found.start, found.end));
+                if self.prompt.is_none() {
+                    self.open_prompt(PromptKind::ReplaceConfirm);
+                }
+                self.scroll_location_into_view();
+                self.needs_render = true;
+            }
+            None => self.finish_replace(),
+        }
+    }
+
+    fn finish_replace(&mut self) {
+        self.prompt = None;
+        self.highlight = None;
+        match self.replace.take() {
+            Some(replace) if replace.count == 0 => self.set_message("No matches replaced."),
+            Some(replace) if replace.count == 1 => self.set_message("Replaced 1 match."),
+            Some(replace) => self.set_message(&format!("Replaced {} matches.", replace.count)),
+            None => {}
+        }
+        self.scroll_location_into_view();
+        self.needs_render = true;
+    }
+
+    fn report_save(&mut self, result: io::Result<()>) {
+        match result {
+            Ok(_) => self.set_message("File saved."),
+            Err(err) => self.set_message(&format!("Could not save file: {err}")),
+        }
+    }
+
+    /// edited_since_swap tells whether the buffer has changed since the swap file was written,
+    /// no matter whether the change came from typing, a prompt or undo.
+    fn edited_since_swap(&self) -> bool {
+        self.buffer.revision() != self.swapped_revision
+    }
+
+    /// write_swap_if_due writes unsaved changes to the swap file at most every SWAP_INTERVAL,
+    /// so that they survive a crash or a killed terminal.
+    fn write_swap_if_due(&mut self) {
+        if !self.buffer.is_modified || !self.edited_since_swap() {
+            return;
+        }
+        if self
+            .swap_written_at
+            .is_some_and(|written_at| written_at.elapsed() < SWAP_INTERVAL)
+        {
+            return;
+        }
+        match self.buffer.write_swap() {
+            Ok(_) => {
+                self.swapped_revision = self.buffer.revision();
+                self.swap_written_at = Some(Instant::now());
+            }
+            Err(err) => {
+                // Retry on the next interval instead of on every keypress
+                self.swap_written_at = Some(Instant::now());
+                self.set_message(&format!("Could not write swap file: {err}"));
+            }
+        }
+    }
+
+    fn undo(&mut self) {
+        match self.buffer.undo() {
+            Some(location) => self.location = location,
+            None => self.set_message("Nothing to undo."),
+        }
+        self.scroll_location_into_view();
+        self.needs_render = true;
+    }
+
+    fn redo(&mut self) {
+        match self.buffer.redo() {
+            Some(location) => self.location = location,
+            None => self.set_message("Nothing to redo."),
+        }
+        self.scroll_location_into_view();
+        self.needs_render = true;
+    }
+
+    /// selection returns the start and the end of the selected text, if any is selected.
+    fn selection(&self) -> Option<(Location, Location)> {
+        let anchor = self.selection_anchor?;
+        match anchor.cmp(&self.location) {
+            Ordering::Less => Some((anchor, self.location)),
+            Ordering::Greater => Some((self.location, anchor)),
+            Ordering::Equal => None,
+        }
+    }
+
+    /// take_selection returns the selected text range and clears the selection.
+    fn take_selection(&mut self) -> Option<(Location, Location)> {
+        let selection = self.selection();
+        if self.selection_anchor.take().is_some() {
+            self.needs_render = true;
+        }
+        selection
+    }
+
+    /// line_range returns the whole line at the caret including its line break. The last line
+    /// has no line break of its own, so the one before it is taken instead.
+    fn line_range(&self) -> (Location, Location) {
+        let line_index = self.location.line_index;
+        let line_end = Location {
+            line_index,
+            grapheme_index: self.buffer.line_grapheme_count(line_index),
+        };
+        if line_index.saturating_add(1) < self.buffer.line_count() {
+            let next_line_start = Location {
+                line_index: line_index.saturating_add(1),
+                grapheme_index: 0,
+            };
+            (
+                Location {
+                    line_index,
+                    grapheme_index: 0,
+                },
+                next_line_start,
+            )
+        } else if line_index > 0 {
+            let previous_line_end = Location {
+                line_index: line_index.saturating_sub(1),
+                grapheme_index: self
+                    .buffer
+                    .line_grapheme_count(line_index.saturating_sub(1)),
+            };
+            (previous_line_end, line_end)
+        } else {
+            (
+                Location {
+                    line_index,
+                    grapheme_index: 0,
+                },
+                line_end,
+            )
+        }
+    }
+
+    /// copy puts the selection, or the caret line without a selection, into the kill ring and
+    /// returns the range it was taken from.
+    fn copy(&mut self) -> (Location, Location) {
+        if let Some((start, end)) = self.selection() {
+            let text = self.buffer.text_between(start, end);
+            self.copy_to_system_clipboard(&text);
+            self.kill_ring.push(Clip {
+                text,
+                is_whole_line: false,
+            });
+            return (start, end);
+        }
+        let line_index = self.location.line_index;
+        let line_start = Location {
+            line_index,
+            grapheme_index: 0,
+        };
+        let line_end = Location {
+            line_index,
+            grapheme_index: self.buffer.line_grapheme_count(line_index),
+        };
+        let text = format!("{}\n", self.buffer.text_between(line_start, line_end));
+        self.copy_to_system_clipboard(&text);
+        self.kill_ring.push(Clip {
+            text,
+            is_whole_line: true,
+        });
+        self.line_range()
+    }
+
+    /// copy_to_system_clipboard hands the text to the host terminal with an OSC 52 sequence.
+    /// The terminal gives no answer, so success cannot be reported.
+    fn copy_to_system_clipboard(&mut self, text: &str) {
+        if !self.osc52.enabled {
+            return;
+        }
+        let command = SetClipboard::new(text);
+        if command.0.len() > self.osc52.max_bytes {
+            self.set_message("Too large for the system clipboard, copied inside the editor only.");
+            return;
+        }
+        if let Err(err) = self.queue_command(command) {
+            self.set_message(&format!("Could not copy to the system clipboard: {err}"));
+        }
+    }
+
+    fn cut(&mut self) {
+        let (start, end) = self.copy();
+        self.take_selection();
+        self.location = self.buffer.delete_range(start, end);
+        self.scroll_location_into_view();
+        self.needs_render = true;
+    }
+
+    /// paste inserts the clip of the given age at the caret, replacing the selection. Whole
+    /// lines go above the caret line.
+    fn paste(&mut self, age: usize) {
+        let Some(clip) = self.kill_ring.get(age).cloned() else {
+            self.set_message("Nothing to paste.");
+            return;
+        };
+        self.buffer.history.start_group();
+        if let Some((start, end)) = self.take_selection() {
+            self.location = self.buffer.delete_range(start, end);
+        }
+        let start = if clip.is_whole_line {
+            Location {
+                line_index: self.location.line_index,
+                grapheme_index: 0,
+            }
+        } else {
+            self.location
+        };
+        let end = self.buffer.insert_text(start, &clip.text);
+        self.buffer.history.end_group();
+        self.location = if clip.is_whole_line {
+            // The caret stays where it was on the line that moved down
+            self.buffer.clamp_location(Location {
+                line_index: end.line_index,
+                grapheme_index: self.location.grapheme_index,
+            })
+        } else {
+            end
+        };
+        self.last_paste = Some((start, end, age));
+        self.scroll_location_into_view();
+        self.needs_render = true;
+    }
+
+    /// insert_pasted_text inserts text pasted into the terminal as it is, in one edit, so
+    /// that a single undo removes it again.
+    fn insert_pasted_text(&mut self, text: &str) {
+        self.location = match self.take_selection() {
+            Some((start, end)) => self.buffer.replace_range(start, end, text),
+            None => self.buffer.insert_text(self.location, text),
+        };
+        self.scroll_location_into_view();
+        self.needs_render = true;
+    }
+
+    /// cycle_paste replaces the text that was just pasted with the next older clip, in a single
+    /// undo step.
+    fn cycle_paste(&mut self) {
+        let Some((start, end, age)) = self.last_paste else {
+            self.set_message("Paste first to cycle through older clips.");
+            return;
+        };
+        self.buffer.history.start_group();
+        self.buffer.delete_range(start, end);
+        self.location = start;
+        self.paste(age.saturating_add(1));
+        self.buffer.history.end_group();
+    }
+
+    /// location_at returns the location drawn at the screen position, or None outside of the
+    /// text area. Positions past the end of a line or of the buffer map to the nearest end.
+    fn location_at(&self, position: Position) -> Option<Location> {
+        if position.y >= self.text_area_size().height {
+            return None;
+        }
+        let line_index = self
+            .scroll_offset
+            .line_index
+            .saturating_add(position.y)
+            .min(self.buffer.line_count().saturating_sub(1));
+        let column = position
+            .x
+            .saturating_sub(self.gutter_width())
+            .saturating_add(self.scroll_offset.column);
+        Some(Location {
+            line_index,
+            grapheme_index: self
+                .buffer
+                .line(line_index)
+                .map_or(0, |line| line.grapheme_index_at(column)),
+        })
+    }
+
+    /// click places the caret at the clicked location. Clicking again at the same spot selects
+    /// the word there, and a third time the whole line.
+    fn click(&mut self, position: Position) {
+        let Some(location) = self.location_at(position) else {
+            return;
+        };
+        let click_count = match self.last_click {
+            Some((clicked_at, last_location, count))
+                if last_location == location && clicked_at.elapsed() < MULTI_CLICK_INTERVAL =>
+            {
+                count % 3 + 1
+            }
+            _ => 1,
+        };
+        self.last_click = Some((Instant::now(), location, click_count));
+        // Typing after clicking starts a new undo step
+        self.buffer.history.seal();
+
+        let line_length = self.buffer.line_grapheme_count(location.line_index);
+        let (start, end) = match click_count {
+            2 => match self
+                .buffer
+                .line(location.line_index)
+                .and_then(|line| line.word_at(location.grapheme_index))
+            {
+                Some(word) => (
+                    Location {
+                        grapheme_index: word.start,
+                        ..location
+                    },
+                    Location {
+                        grapheme_index: word.end,
+                        ..location
+                    },
+                ),
+                None => (location, location),
+            },
+            3 if location.line_index.saturating_add(1) < self.buffer.line_count() => (
+                Location {
+                    grapheme_index: 0,
+                    ..location
+                },
+                Location {
+                    line_index: location.line_index.saturating_add(1),
+                    grapheme_index: 0,
+                },
+            ),
+            3 => (
+                Location {
+                    grapheme_index: 0,
+                    ..location
+                },
+                Location {
+                    grapheme_index: line_length,
+                    ..location
+                },
+            ),
+            _ => (location, location),
+        };
+        // Dragging from here extends the selection
+        self.selection_anchor = Some(start);
+        self.location = end;
+        self.scroll_location_into_view();
+        self.needs_render = true;
+    }
+
+    fn drag(&mut self, position: Position) {
+        let Some(location) = self.location_at(position) else {
+            return;
+        };
+        if self.selection_anchor.is_none() {
+            self.selection_anchor = Some(self.location);
+        }
+        self.location = location;
+        self.scroll_location_into_view();
+        self.needs_render = true;
+    }
+
+    /// scroll_lines moves the view without moving the caret, which may end up out of view.
+    fn scroll_lines(&mut self, down: bool) {
+        let last_line_index = self.buffer.line_count().saturating_sub(1);
+        self.scroll_offset.line_index = if down {
+            self.scroll_offset
+                .line_index
+                .saturating_add(SCROLL_LINES)
+                .min(last_line_index)
+        } else {
+            self.scroll_offset.line_index.saturating_sub(SCROLL_LINES)
+        };
+        self.needs_render = true;
+    }
+
+    /// go_to moves the caret to the location, or the nearest one that exists, and scrolls so
+    /// that its line is centered vertically. It tells the user when the requested location does
+    /// not exist.
+    pub fn go_to(&mut self, location: Location) {
+        self.location = self.buffer.clamp_location(location);
+        if self.location.line_index < location.line_index {
+            self.set_message(&format!(
+                "The file has only {} lines.",
+                self.buffer.line_count()
+            ));
+        } else if self.location.grapheme_index < location.grapheme_index {
+            self.set_message(&format!(
+                "Line {} ends at column {}.",
+                self.location.line_index.saturating_add(1),
+                self.location.grapheme_index.saturating_add(1)
+            ));
+        }
+        let Size { height, .. } = self.text_area_size();
+        self.scroll_offset.line_index = self.location.line_index.saturating_sub(height / 2);
+        self.scroll_location_into_view();
+        self.needs_render = true;
+    }
+
+    /// caret_column returns the screen column of the caret within its line, before scrolling.
+    fn caret_column(&self) -> usize {
+        self.buffer
+            .line(self.location.line_index)
+            .map_or(0, |line| line.column_of(self.location.grapheme_index))
+    }
+
+    /// move_to_line moves the caret to the given line, as close as possible to the screen
+    /// column it was in when vertical movement started.
+    fn move_to_line(&mut self, line_index: usize) {
+        let column = self.desired_column.unwrap_or_else(|| self.caret_column());
+        self.desired_column = Some(column);
+        self.location = Location {
+            line_index,
+            grapheme_index: self
+                .buffer
+                .line(line_index)
+                .map_or(0, |line| line.grapheme_index_at(column)),
+        };
+    }
+
     /// scroll_location_into_view scrolls the current location into view if it's outside the
     /// current view.
     /// row 0
@@ -93,15 +1097,22 @@ impl Terminal {
     /// row 5    ← target_row    ← currently outside view
     /// row 6
     fn scroll_location_into_view(&mut self) {
-        let Location {
-            line_index: target_row,
-            grapheme_index: target_col,
-        } = self.location;
-        let Location {
+        let target_row = self.location.line_index;
+        // Tabs and wide graphemes take more than one column, and all of it must be visible
+        let target_col = self.caret_column();
+        let target_col_end = self
+            .buffer
+            .line(target_row)
+            .map_or(0, |line| {
+                line.column_of(self.location.grapheme_index.saturating_add(1))
+            })
+            .max(target_col.saturating_add(1));
+        let ScrollOffset {
             line_index: offset_row,
-            grapheme_index: offset_col,
+            column: offset_col,
         } = self.scroll_offset;
-        let Size { width, height } = self.size().unwrap_or_default();
+        let Size { width, height } = self.text_area_size();
+        let width = width.saturating_sub(self.gutter_width());
 
         // Scroll vertically
         if target_row < offset_row {
@@ -114,57 +1125,86 @@ impl Terminal {
 
         // Scroll horizontally
         if target_col < offset_col {
-            self.scroll_offset.grapheme_index = target_col;
+            self.scroll_offset.column = target_col;
             self.needs_render = true;
-        } else if target_col >= offset_col.saturating_add(width) {
-            self.scroll_offset.grapheme_index = target_col.saturating_sub(width).saturating_add(1);
+        } else if target_col_end > offset_col.saturating_add(width) {
+            self.scroll_offset.column = target_col_end.saturating_sub(width);
             self.needs_render = true;
         }
     }
 
     pub fn enter_alternate_screen(&self) -> io::Result<&Self> {
         self.queue_command(terminal::EnterAlternateScreen)?
-            .flush()?;
-        Ok(&self)
+            .queue_command(event::EnableBracketedPaste)?
+            .queue_command(event::EnableMouseCapture)?;
+        if self.keyboard_enhanced {
+            self.queue_command(event::PushKeyboardEnhancementFlags(
+                KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES,
+            ))?;
+        }
+        self.flush()?;
+        Ok(self)
     }
 
     pub fn handle_ordinary_typing(&mut self, char: Option<char>) -> io::Result<()> {
-        match char {
-            None => return Ok(()),
-            Some(c) => {
-                let old_len = self
-                    .buffer
-                    .lines
-                    .get(self.location.line_index)
-                    .map_or(0, Line::grapheme_count);
-                self.buffer.insert_char(c, self.location);
-                let new_len = self
-                    .buffer
-                    .lines
-                    .get(self.location.line_index)
-                    .map_or(0, Line::grapheme_count);
-                if new_len.saturating_sub(old_len) > 0 {
-                    self.move_caret_to_location(Direction::Right)?;
-                }
-                self.needs_render = true;
-                return Ok(());
-            }
+        let Some(c) = char else {
+            return Ok(());
+        };
+        // Typing over a selection replaces it in a single undo step. Without a selection the
+        // character is left to be merged with the word being typed.
+        let selection = self.take_selection();
+        if let Some((start, end)) = selection {
+            self.buffer.history.start_group();
+            self.location = self.buffer.delete_range(start, end);
         }
+        if c == '\t' && self.buffer.indentation.expand_tabs {
+            let spaces = self
+                .buffer
+                .indentation
+                .spaces_to_next_tab_stop(self.caret_column());
+            self.location = self.buffer.insert_text(self.location, &" ".repeat(spaces));
+        } else {
+            self.location = self.buffer.insert_char(c, self.location);
+        }
+        if selection.is_some() {
+            self.buffer.history.end_group();
+        }
+        self.scroll_location_into_view();
+        self.needs_render = true;
+        Ok(())
     }
 
     pub fn handle_special_key(&mut self, special_key: SpecialKey) -> io::Result<()> {
-        let current_caret_line = self.location.line_index;
-        let current_caret_col = self.location.grapheme_index;
         // let last_line_index = self.buffer.line_count().saturating_sub(1);
-        // let last_line_len = match self.buffer.lines.get(last_line_index) {
+        // let last_line_len = match self.buffer.line(last_line_index) {
         //     Some(line) => line.fragments.len(),
         //     None => 0,
         // };
 
+        if let Some((start, end)) = self.take_selection() {
+            match special_key {
+                SpecialKey::Backspace
+                | SpecialKey::Delete
+                | SpecialKey::CtrlBackspace
+                | SpecialKey::CtrlDelete => {
+                    self.location = self.buffer.delete_range(start, end);
+                    self.scroll_location_into_view();
+                    return Ok(());
+                }
+                SpecialKey::Enter | SpecialKey::ShiftEnter => {
+                    self.location = self.buffer.replace_range(start, end, "\n");
+                    self.scroll_location_into_view();
+                    return Ok(());
+                }
+                // Anything else, like Escape, only drops the selection
+                _ => {}
+            }
+        }
+
         match special_key {
-            SpecialKey::Enter => {
-                self.buffer.insert_newline(self.location);
-                self.move_caret_to_location(Direction::Right)?;
+            SpecialKey::Enter | SpecialKey::ShiftEnter => {
+                self.location = self.buffer.insert_newline(self.location);
+                self.scroll_location_into_view();
                 self.needs_render = true;
             }
             // SpecialKey::Tab => {
@@ -187,6 +1227,16 @@ impl Terminal {
             //     };
             // }
             SpecialKey::BackTab => todo!(),
+            SpecialKey::Escape => {}
+            SpecialKey::CtrlBackspace => {
+                self.location = self.buffer.delete_word_before(self.location);
+                self.scroll_location_into_view();
+                self.needs_render = true;
+            }
+            SpecialKey::CtrlDelete => {
+                self.buffer.delete_word_after(self.location);
+                self.needs_render = true;
+            }
             SpecialKey::Delete => {
                 self.buffer.delete(self.location);
                 self.needs_render = true;
@@ -241,40 +1291,9 @@ impl Terminal {
                 // }
             }
             SpecialKey::Backspace => {
-                let line = match self.buffer.lines.get_mut(current_caret_line) {
-                    Some(line) => line,
-                    None => return Ok(()),
-                };
-                // Normal backspace within a line
-                if current_caret_col != 0 {
-                    line.fragments.remove(current_caret_col.saturating_sub(1));
-                    self.move_caret_to_location(Direction::Left)?;
-                    self.needs_render = true;
-                    return Ok(());
-                }
-                // Top left of the document should do nothing
-                if current_caret_line == 0 && current_caret_col == 0 {
-                    self.needs_render = true;
-                    return Ok(());
-                }
-                if current_caret_line != 0 && current_caret_col == 0 {
-                    let mut fragments_to_move = line.fragments.split_off(0);
-                    // Merge with previous line
-                    let previous_line_index = current_caret_line.saturating_sub(1);
-                    if let Some(previous_line) = self.buffer.lines.get_mut(previous_line_index) {
-                        previous_line.fragments.append(&mut fragments_to_move);
-                    }
-                    // Delete the current line
-                    self.buffer.lines.remove(current_caret_line);
-                    self.location.line_index = previous_line_index;
-                    self.location.grapheme_index = match self.buffer.lines.get(previous_line_index)
-                    {
-                        Some(prev_line) => prev_line.fragments.len(),
-                        None => 0,
-                    };
-                    self.needs_render = true;
-                    return Ok(());
-                }
+                self.location = self.buffer.backspace(self.location);
+                self.scroll_location_into_view();
+                self.needs_render = true;
             }
             SpecialKey::Insert => todo!(),
             SpecialKey::CapsLock => todo!(),
@@ -341,6 +1360,11 @@ impl Terminal {
 
 impl View for Terminal {
     fn terminate(&self) -> io::Result<()> {
+        if self.keyboard_enhanced {
+            self.queue_command(event::PopKeyboardEnhancementFlags)?;
+        }
+        self.queue_command(event::DisableMouseCapture)?;
+        self.queue_command(event::DisableBracketedPaste)?;
         self.queue_command(terminal::LeaveAlternateScreen)?;
         self.show_caret()?;
         self.flush()?;
@@ -348,44 +1372,20 @@ impl View for Terminal {
     }
 
     fn move_caret_to_location(&mut self, direction: Direction) -> io::Result<()> {
-        let Size { height, .. } = self.size()?;
-        if let Some(curr_line) = self.buffer.lines.get(self.location.line_index) {
+        let Size { height, .. } = self.text_area_size();
+        if let Some(curr_line) = self.buffer.line(self.location.line_index) {
             let (row, col) = (self.location.line_index, self.location.grapheme_index);
             match direction {
                 Direction::Up => {
-                    // Move up within the document
-                    self.location.line_index = if row > 0 {
-                        let line_index = self.location.line_index.saturating_sub(1);
-
-                        // If the current column is beyond the longest column in the new line,
-                        // adjust it to the end of that line
-                        let longest_col_in_line = match self.buffer.lines.get(line_index) {
-                            Some(line) => line.graphemes_width(),
-                            None => 0,
-                        };
-                        if self.location.grapheme_index > longest_col_in_line {
-                            self.location.grapheme_index = longest_col_in_line;
-                        }
-                        line_index
-                    } else {
-                        // Stay at the top if already at row
-                        0
+                    // Stay at the top if already at row
+                    if row > 0 {
+                        self.move_to_line(row.saturating_sub(1));
                     }
                 }
                 Direction::Down => {
                     // Move down within the document
                     if row < self.buffer.line_count().saturating_sub(1) {
-                        self.location.line_index = row.saturating_add(1);
-                        // If the current column is beyond the longest column in the new line,
-                        // adjust it to the end of that line
-                        let longest_col_in_line =
-                            match self.buffer.lines.get(self.location.line_index) {
-                                Some(line) => line.graphemes_width(),
-                                None => 0,
-                            };
-                        if self.location.grapheme_index > longest_col_in_line {
-                            self.location.grapheme_index = longest_col_in_line;
-                        }
+                        self.move_to_line(row.saturating_add(1));
                     }
                 }
                 Direction::Left => {
@@ -396,46 +1396,44 @@ impl View for Terminal {
                     // Move to end of previous line if at beginning of current line
                     if col == 0 && row > 0 {
                         self.location.line_index = row.saturating_sub(1);
-                        if let Some(prev_line) = self.buffer.lines.get(self.location.line_index) {
-                            self.location.grapheme_index =
-                                prev_line.fragments.len().saturating_sub(1);
+                        if let Some(prev_line) = self.buffer.line(self.location.line_index) {
+                            self.location.grapheme_index = prev_line.grapheme_count();
                         }
                     }
                 }
                 Direction::Right => {
                     // Move right within the current line
-                    if col < curr_line.graphemes_width().saturating_sub(1) {
+                    if col < curr_line.grapheme_count() {
                         self.location.grapheme_index = col.saturating_add(1);
                     }
                     // Move to beginning of next line if at end of current line
-                    if col >= curr_line.graphemes_width().saturating_sub(1) {
-                        if let Some(_) = self.buffer.lines.get(row.saturating_add(1)) {
-                            self.location.line_index = row.saturating_add(1);
-                            self.location.grapheme_index = 0;
-                        }
+                    if col >= curr_line.grapheme_count()
+                        && self.buffer.line(row.saturating_add(1)).is_some()
+                    {
+                        self.location.line_index = row.saturating_add(1);
+                        self.location.grapheme_index = 0;
                     }
                 }
                 Direction::PageUp => {
                     // Move up by one page, but do not exceed top of document
-                    if row < height {
-                        self.location.line_index = 0;
-                    } else {
-                        self.location.line_index = row.saturating_sub(height);
-                    }
+                    self.move_to_line(row.saturating_sub(height));
                 }
                 Direction::PageDown => {
                     // Move down by one page, but do not exceed buffer line count
-                    if self.buffer.line_count() > row.saturating_add(height) {
-                        self.location.line_index = row.saturating_add(height);
-                    } else {
-                        self.location.line_index = self.buffer.line_count().saturating_sub(1);
-                    }
+                    let last_line_index = self.buffer.line_count().saturating_sub(1);
+                    self.move_to_line(row.saturating_add(height).min(last_line_index));
+                }
+                Direction::WordLeft => {
+                    self.location = self.buffer.previous_word_start(self.location);
+                }
+                Direction::WordRight => {
+                    self.location = self.buffer.next_word_end(self.location);
                 }
                 Direction::Home => {
                     self.location.grapheme_index = 0;
                 }
                 Direction::End => {
-                    self.location.grapheme_index = curr_line.fragments.len() - 1;
+                    self.location.grapheme_index = curr_line.grapheme_count();
                 }
             }
         };
@@ -489,28 +1487,53 @@ impl View for Terminal {
     /// row 5
     /// row 6
     fn render(&mut self) -> io::Result<()> {
-        if !self.needs_render {
+        let Size { width, height } = self.text_area_size();
+        if width == 0 || height == 0 {
             return Ok(());
         }
-        let Size { width, height } = self.size()?;
-        if width == 0 || width == 0 {
+        // Caret movements only change the status bar, not the text
+        let status = self.document_status().render_text(width);
+        if self.needs_render || status != self.rendered_status {
+            self.move_caret_to_position(Position { x: 0, y: height })?;
+            self.queue_command(style::SetAttribute(style::Attribute::Reverse))?;
+            self.print(status.as_str())?;
+            self.queue_command(style::SetAttribute(style::Attribute::NoReverse))?;
+            self.rendered_status = status;
+        }
+        // Relative line numbers change with every line the caret moves
+        if self.gutter.line_numbers == LineNumbers::Relative
+            && self.location.line_index != self.rendered_caret_line
+        {
+            self.needs_render = true;
+        }
+        if !self.needs_render {
             return Ok(());
         }
+        let gutter_width = self.gutter_width();
+        let text_width = width.saturating_sub(gutter_width);
         let top = self.scroll_offset.line_index;
         for view_row in 0..height {
             let abs_view_row = view_row.saturating_add(top);
-            match self.buffer.lines.get(abs_view_row) {
+            match self.buffer.line(abs_view_row) {
                 Some(line) => {
-                    let left = self.scroll_offset.grapheme_index;
-                    let right = if left.saturating_add(width) > line.graphemes_width() {
+                    let left = self.scroll_offset.column;
+                    let right = if left.saturating_add(text_width) > line.graphemes_width() {
                         line.graphemes_width()
                     } else {
-                        left.saturating_add(width)
+                        left.saturating_add(text_width)
                     };
-                    let content_in_view = line.get_visible_graphemes(left..right);
+                    let highlight = self.highlight_in_line(abs_view_row, &line);
                     self.move_caret_to_position(Position { x: 0, y: view_row })?;
                     self.clear_line()?;
-                    self.print(content_in_view.as_str())?;
+                    if gutter_width > 0 {
+                        let number = self.gutter.render_number(
+                            abs_view_row,
+                            self.location.line_index,
+                            gutter_width,
+                        );
+                        self.print(number.as_str())?;
+                    }
+                    self.print_line(&line, left..right, highlight)?;
                 }
                 None => {
                     // Show the welcome message if we're at 1/3rd of the screen height
@@ -528,6 +1551,15 @@ impl View for Terminal {
                 }
             }
         }
+        let prompt_text = self.prompt.as_ref().map(|prompt| prompt.render_text(width));
+        let message_bar = self.message_bar.render_text(prompt_text, width);
+        self.move_caret_to_position(Position {
+            x: 0,
+            y: height.saturating_add(1),
+        })?;
+        self.clear_line()?;
+        self.print(message_bar.as_str())?;
+        self.rendered_caret_line = self.location.line_index;
         self.needs_render = false;
 
         Ok(())
@@ -544,23 +1576,117 @@ impl View for Terminal {
     }
 
     fn handle_command(&mut self, command: TerminalCommand) -> io::Result<()> {
+        if !matches!(
+            command,
+            TerminalCommand::Resize(_)
+                | TerminalCommand::MoveCaret(
+                    Direction::Up | Direction::Down | Direction::PageUp | Direction::PageDown
+                )
+                | TerminalCommand::ExtendSelection(
+                    Direction::Up | Direction::Down | Direction::PageUp | Direction::PageDown
+                )
+        ) {
+            self.desired_column = None;
+        }
+        if !matches!(
+            command,
+            TerminalCommand::Resize(_) | TerminalCommand::CyclePaste
+        ) {
+            self.last_paste = None;
+        }
+        // Edits act on the selection themselves, everything else but selecting drops it
+        if !matches!(
+            command,
+            TerminalCommand::Resize(_)
+                | TerminalCommand::ExtendSelection(_)
+                | TerminalCommand::Drag(_)
+                | TerminalCommand::ScrollUp
+                | TerminalCommand::ScrollDown
+                | TerminalCommand::Cut
+                | TerminalCommand::Copy
+                | TerminalCommand::Paste
+                | TerminalCommand::PasteText(_)
+                | TerminalCommand::OrdinaryChar(_)
+                | TerminalCommand::SpecialKey(_)
+        ) {
+            self.take_selection();
+        }
+        if self.prompt.is_some() && !matches!(command, TerminalCommand::Resize(_)) {
+            return self.handle_prompt_command(command);
+        }
         match command {
-            TerminalCommand::MoveCaret(direction) => match self.move_caret_to_location(direction) {
-                Ok(_) => Ok(()),
-                Err(err) => Err(err),
-            },
+            TerminalCommand::MoveCaret(direction) => {
+                // Typing after moving the caret starts a new undo step
+                self.buffer.history.seal();
+                match self.move_caret_to_location(direction) {
+                    Ok(_) => Ok(()),
+                    Err(err) => Err(err),
+                }
+            }
+            TerminalCommand::ExtendSelection(direction) => {
+                self.buffer.history.seal();
+                if self.selection_anchor.is_none() {
+                    self.selection_anchor = Some(self.location);
+                }
+                self.needs_render = true;
+                self.move_caret_to_location(direction)
+            }
+            TerminalCommand::Cut => {
+                self.cut();
+                Ok(())
+            }
+            TerminalCommand::Copy => {
+                self.copy();
+                Ok(())
+            }
+            TerminalCommand::Paste => {
+                self.paste(0);
+                Ok(())
+            }
+            TerminalCommand::CyclePaste => {
+                self.cycle_paste();
+                Ok(())
+            }
+            TerminalCommand::PasteText(text) => {
+                self.insert_pasted_text(&text);
+                Ok(())
+            }
+            TerminalCommand::Click(position) => {
+                self.click(position);
+                Ok(())
+            }
+            TerminalCommand::Drag(position) => {
+                self.drag(position);
+                Ok(())
+            }
+            TerminalCommand::ScrollUp => {
+                self.scroll_lines(false);
+                Ok(())
+            }
+            TerminalCommand::ScrollDown => {
+                self.scroll_lines(true);
+                Ok(())
+            }
+            TerminalCommand::Undo => {
+                self.undo();
+                Ok(())
+            }
+            TerminalCommand::Redo => {
+                self.redo();
+                Ok(())
+            }
             TerminalCommand::OrdinaryChar(key_code) => {
                 let c = key_code.as_char();
-                match self.handle_ordinary_typing(c) {
-                    Ok(_) => Ok(()),
-                    Err(_) => Ok(()), // Just ignore the error for now
+                if let Err(err) = self.handle_ordinary_typing(c) {
+                    self.set_message(&format!("Could not insert text: {err}"));
                 }
+                Ok(())
             }
             TerminalCommand::SpecialKey(key_code) => {
-                match self.handle_special_key(key_code) {
-                    Ok(_) => Ok(()),
-                    Err(_) => Ok(()), // Just ignore the error for now
+                if let Err(err) = self.handle_special_key(key_code) {
+                    self.set_message(&format!("Could not edit text: {err}"));
                 }
+                Ok(())
             }
             // TerminalCommand::InsertChar(char) => {
             //     match self.handle_ordinary_typing(Some(char)) {
@@ -568,8 +1694,47 @@ impl View for Terminal {
             //         Err(_) => Ok(()), // Just ignore the error for now
             //     }
             // }
-            TerminalCommand::FunctionKey(n) => Ok(()),
-            TerminalCommand::Resize(size) => Ok(self.resize(size)),
+            TerminalCommand::SaveAs => {
+                self.open_prompt(PromptKind::SaveAs);
+                Ok(())
+            }
+            TerminalCommand::Search => {
+                self.open_prompt(PromptKind::Search);
+                Ok(())
+            }
+            TerminalCommand::Replace => {
+                self.open_prompt(PromptKind::ReplacePattern);
+                Ok(())
+            }
+            TerminalCommand::GoToLine => {
+                self.open_prompt(PromptKind::GoToLine);
+                Ok(())
+            }
+            TerminalCommand::Open => {
+                self.open_prompt(PromptKind::Open);
+                Ok(())
+            }
+            TerminalCommand::NextBuffer => {
+                self.cycle_documents(true);
+                Ok(())
+            }
+            TerminalCommand::PreviousBuffer => {
+                self.cycle_documents(false);
+                Ok(())
+            }
+            TerminalCommand::ListBuffers => {
+                self.list_documents();
+                Ok(())
+            }
+            TerminalCommand::ToggleRelativeLineNumbers => {
+                self.toggle_relative_line_numbers();
+                Ok(())
+            }
+            TerminalCommand::FunctionKey(_) => Ok(()),
+            TerminalCommand::Resize(size) => {
+                self.resize(size);
+                Ok(())
+            }
             _ => Ok(()),
         }
     }
@@ -578,10 +1743,26 @@ impl View for Terminal {
     where
         F: FnMut(EditorCommand),
     {
+        self.write_swap_if_due();
+        if self.message_bar.clear_expired() {
+            self.needs_render = true;
+        }
+        if !poll(POLL_TIMEOUT)? {
+            return Ok(());
+        }
+
         let (event, should_proceed) = match read() {
             Ok(event) => match event {
-                Event::Key(KeyEvent { kind, .. }) if kind == KeyEventKind::Press => (event, true),
-                Event::Resize(_, _) => (event, true),
+                Event::Key(KeyEvent {
+                    kind: KeyEventKind::Press,
+                    ..
+                }) => (event, true),
+                // Mouse capture reports every movement, and releasing a button does nothing
+                Event::Mouse(MouseEvent {
+                    kind: MouseEventKind::Moved | MouseEventKind::Up(_),
+                    ..
+                }) => (event, false),
+                Event::Resize(_, _) | Event::Paste(_) | Event::Mouse(_) => (event, true),
                 _ => (event, false),
             },
             Err(err) => return Err(err),
@@ -591,11 +1772,58 @@ impl View for Terminal {
             return Ok(());
         }
 
-        match TerminalCommand::try_from(event) {
-            Ok(command) if matches!(command, TerminalCommand::Quit) => {
+        let command = TerminalCommand::try_from(event);
+        // Unbound keys and scrolling leave the warning up, so that Ctrl-Q still quits after them
+        if !matches!(
+            command,
+            Ok(TerminalCommand::Resize(_)
+                | TerminalCommand::Quit
+                | TerminalCommand::Unknown
+                | TerminalCommand::ScrollUp
+                | TerminalCommand::ScrollDown)
+        ) && self.quit_pending
+        {
+            // The warning about unsaved changes no longer applies
+            self.quit_pending = false;
+            self.message_bar.clear();
+            self.needs_render = true;
+        }
+
+        let is_recovery_pending = self
+            .prompt
+            .as_ref()
+            .is_some_and(|prompt| prompt.kind == PromptKind::RecoverSwap);
+        match command {
+            // Saving would make the swap file look outdated, and quitting would leave the
+            // question unanswered
+            Ok(TerminalCommand::Quit | TerminalCommand::Save) if is_recovery_pending => {
+                self.set_message(RECOVERY_HINT);
+            }
+            Ok(TerminalCommand::Quit)
+                if self.buffers().any(|buffer| buffer.is_modified) && !self.quit_pending =>
+            {
+                self.quit_pending = true;
+                let modified_count = self.buffers().filter(|buffer| buffer.is_modified).count();
+                if modified_count == 1 {
+                    self.set_message("File has unsaved changes. Press Ctrl-Q again to quit.");
+                } else {
+                    self.set_message(&format!(
+                        "{modified_count} files have unsaved changes. Press Ctrl-Q again to quit."
+                    ));
+                }
+            }
+            Ok(TerminalCommand::Quit) => {
+                // Quitting discards unsaved changes on purpose. Swap files this session did not
+                // write, like those of documents that were never shown, are kept.
+                for buffer in self.buffers() {
+                    let _ = buffer.remove_swap();
+                }
                 self.terminate()?;
                 action(EditorCommand::Quit);
             }
+            Ok(TerminalCommand::Save) => {
+                action(EditorCommand::Save);
+            }
             Ok(command) if !matches!(command, TerminalCommand::Unknown) => {
                 self.handle_command(command)?;
             }
@@ -611,7 +1839,52 @@ impl View for Terminal {
         Ok(())
     }
 
-    fn get_position(&mut self) -> io::Result<Position> {
-        Ok(self.location.to_position(self.scroll_offset))
+    fn get_position(&mut self) -> io::Result<Option<Position>> {
+        // While confirming a replacement the caret stays on the match
+        if let Some(prompt) = &self.prompt
+            && prompt.kind != PromptKind::ReplaceConfirm
+        {
+            return Ok(Some(Position {
+                x: prompt.caret_x(self.size.width),
+                y: self.text_area_size().height.saturating_add(1),
+            }));
+        }
+        // The mouse wheel scrolls without moving the caret
+        let visible_lines = self.scroll_offset.line_index
+            ..self
+                .scroll_offset
+                .line_index
+                .saturating_add(self.text_area_size().height);
+        if !visible_lines.contains(&self.location.line_index) {
+            return Ok(None);
+        }
+        let line = self
+            .buffer
+            .line(self.location.line_index)
+            .unwrap_or_default();
+        Ok(Some(self.location.to_position(
+            &line,
+            self.scroll_offset,
+            self.gutter_width(),
+        )))
+    }
+
+    fn save(&mut self) {
+        if self.buffer.file_path.is_none() {
+            self.open_prompt(PromptKind::SaveAs);
+            return;
+        }
+        let result = self.buffer.save();
+        self.report_save(result);
+    }
+
+    fn write_swap(&self) -> io::Result<Vec<PathBuf>> {
+        let mut swap_paths = Vec::new();
+        for buffer in self.buffers().filter(|buffer| buffer.is_modified) {
+            if let Some(swap_path) = buffer.write_swap()? {
+                swap_paths.push(swap_path);
+            }
+        }
+        Ok(swap_paths)
     }
 }
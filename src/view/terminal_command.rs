@@ -1,5 +1,8 @@
-use crate::view::Size;
-use crossterm::event::{Event as CrossTermEvent, KeyCode, KeyEvent, KeyModifiers};
+use crate::view::{Position, Size};
+use crossterm::event::{
+    Event as CrossTermEvent, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent,
+    MouseEventKind,
+};
 
 pub enum Direction {
     Up,
@@ -10,25 +13,56 @@ pub enum Direction {
     PageDown,
     Home,
     End,
+    WordLeft,
+    WordRight,
 }
 
 pub enum SpecialKey {
     Backspace,
     Delete,
+    CtrlBackspace,
+    CtrlDelete,
     Enter,
+    ShiftEnter,
+    Escape,
     // Tab,
     BackTab,
     CapsLock,
     Insert,
 }
 
+impl Direction {
+    /// from_key returns the direction of a navigation key, or None for any other key. Ctrl
+    /// turns the left and right arrows into word motions.
+    fn from_key(code: KeyCode, modifiers: KeyModifiers) -> Option<Self> {
+        let by_word = modifiers.contains(KeyModifiers::CONTROL);
+        match code {
+            KeyCode::Left if by_word => Some(Direction::WordLeft),
+            KeyCode::Right if by_word => Some(Direction::WordRight),
+            KeyCode::Left => Some(Direction::Left),
+            KeyCode::Right => Some(Direction::Right),
+            KeyCode::Up => Some(Direction::Up),
+            KeyCode::Down => Some(Direction::Down),
+            KeyCode::Home => Some(Direction::Home),
+            KeyCode::End => Some(Direction::End),
+            KeyCode::PageUp => Some(Direction::PageUp),
+            KeyCode::PageDown => Some(Direction::PageDown),
+            _ => None,
+        }
+    }
+}
+
 impl PartialEq for SpecialKey {
     fn eq(&self, other: &Self) -> bool {
         matches!(
             (self, other),
             (SpecialKey::Backspace, SpecialKey::Backspace)
                 | (SpecialKey::Delete, SpecialKey::Delete)
+                | (SpecialKey::CtrlBackspace, SpecialKey::CtrlBackspace)
+                | (SpecialKey::CtrlDelete, SpecialKey::CtrlDelete)
                 | (SpecialKey::Enter, SpecialKey::Enter)
+                | (SpecialKey::ShiftEnter, SpecialKey::ShiftEnter)
+                | (SpecialKey::Escape, SpecialKey::Escape)
                 // | (SpecialKey::Tab, SpecialKey::Tab)
                 | (SpecialKey::BackTab, SpecialKey::BackTab)
                 | (SpecialKey::CapsLock, SpecialKey::CapsLock)
@@ -39,10 +73,38 @@ impl PartialEq for SpecialKey {
 
 pub enum TerminalCommand {
     MoveCaret(Direction),
+    // Moves the caret while the selection stays anchored where it started
+    ExtendSelection(Direction),
     Resize(Size),
     Quit,
+    Save,
+    SaveAs,
+    Undo,
+    Redo,
+    Cut,
+    Copy,
+    Paste,
+    // Replaces the text that was just pasted with the next older clip
+    CyclePaste,
+    // Text pasted into the terminal, with its line breaks turned into '\n'
+    PasteText(String),
+    // Left mouse button pressed at a screen position
+    Click(Position),
+    // Mouse moved with the left button held down, or Shift-clicked
+    Drag(Position),
+    ScrollUp,
+    ScrollDown,
+    Search,
+    Replace,
+    GoToLine,
+    Open,
+    NextBuffer,
+    PreviousBuffer,
+    ListBuffers,
+    ToggleRelativeLineNumbers,
     Unknown,
     OrdinaryChar(KeyCode),
+    #[allow(dead_code)] // Not bound to any action yet
     FunctionKey(u8),
     SpecialKey(SpecialKey),
 }
@@ -55,26 +117,111 @@ impl TryFrom<CrossTermEvent> for TerminalCommand {
             CrossTermEvent::Key(KeyEvent {
                 code, modifiers, ..
             }) => match code {
-                KeyCode::Left => Ok(Self::MoveCaret(Direction::Left)),
-                KeyCode::Right => Ok(Self::MoveCaret(Direction::Right)),
-                KeyCode::Up => Ok(Self::MoveCaret(Direction::Up)),
-                KeyCode::Down => Ok(Self::MoveCaret(Direction::Down)),
-                KeyCode::Home => Ok(Self::MoveCaret(Direction::Home)),
-                KeyCode::End => Ok(Self::MoveCaret(Direction::End)),
-                KeyCode::PageUp => Ok(Self::MoveCaret(Direction::PageUp)),
-                KeyCode::PageDown => Ok(Self::MoveCaret(Direction::PageDown)),
+                KeyCode::Left
+                | KeyCode::Right
+                | KeyCode::Up
+                | KeyCode::Down
+                | KeyCode::Home
+                | KeyCode::End
+                | KeyCode::PageUp
+                | KeyCode::PageDown => match Direction::from_key(code, modifiers) {
+                    Some(direction) if modifiers.contains(KeyModifiers::SHIFT) => {
+                        Ok(Self::ExtendSelection(direction))
+                    }
+                    Some(direction) => Ok(Self::MoveCaret(direction)),
+                    None => Ok(Self::Unknown),
+                },
                 KeyCode::Char('q') if modifiers.contains(KeyModifiers::CONTROL) => Ok(Self::Quit),
+                KeyCode::Char('s') if modifiers.contains(KeyModifiers::CONTROL) => Ok(Self::Save),
+                KeyCode::Char('s') if modifiers.contains(KeyModifiers::ALT) => Ok(Self::SaveAs),
+                KeyCode::Char('z') if modifiers.contains(KeyModifiers::CONTROL) => Ok(Self::Undo),
+                KeyCode::Char('y') if modifiers.contains(KeyModifiers::CONTROL) => Ok(Self::Redo),
+                KeyCode::Char('x') if modifiers.contains(KeyModifiers::CONTROL) => Ok(Self::Cut),
+                KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => Ok(Self::Copy),
+                KeyCode::Char('v') if modifiers.contains(KeyModifiers::CONTROL) => Ok(Self::Paste),
+                KeyCode::Char('v') if modifiers.contains(KeyModifiers::ALT) => Ok(Self::CyclePaste),
+                KeyCode::Char('f') if modifiers.contains(KeyModifiers::CONTROL) => Ok(Self::Search),
+                KeyCode::Char('r') if modifiers.contains(KeyModifiers::CONTROL) => {
+                    Ok(Self::Replace)
+                }
+                KeyCode::Char('g') if modifiers.contains(KeyModifiers::CONTROL) => {
+                    Ok(Self::GoToLine)
+                }
+                KeyCode::Char('o') if modifiers.contains(KeyModifiers::CONTROL) => Ok(Self::Open),
+                KeyCode::Char('n') if modifiers.contains(KeyModifiers::ALT) => Ok(Self::NextBuffer),
+                KeyCode::Char('p') if modifiers.contains(KeyModifiers::ALT) => {
+                    Ok(Self::PreviousBuffer)
+                }
+                KeyCode::Char('b') if modifiers.contains(KeyModifiers::ALT) => {
+                    Ok(Self::ListBuffers)
+                }
+                KeyCode::Char('l') if modifiers.contains(KeyModifiers::ALT) => {
+                    Ok(Self::ToggleRelativeLineNumbers)
+                }
                 KeyCode::F(n) if (1..=12).contains(&n) => Ok(Self::FunctionKey(n)),
+                // Ctrl-Backspace is only told apart from Backspace with keyboard enhancements,
+                // so Alt-Backspace and Ctrl-W delete a word as well
+                KeyCode::Backspace
+                    if modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+                {
+                    Ok(Self::SpecialKey(SpecialKey::CtrlBackspace))
+                }
+                // xterm, VTE and konsole send Ctrl-H for Ctrl-Backspace
+                KeyCode::Char('w' | 'h') if modifiers.contains(KeyModifiers::CONTROL) => {
+                    Ok(Self::SpecialKey(SpecialKey::CtrlBackspace))
+                }
+                KeyCode::Delete if modifiers.contains(KeyModifiers::CONTROL) => {
+                    Ok(Self::SpecialKey(SpecialKey::CtrlDelete))
+                }
                 KeyCode::Backspace => Ok(Self::SpecialKey(SpecialKey::Backspace)),
                 KeyCode::Delete => Ok(Self::SpecialKey(SpecialKey::Delete)),
+                // Terminals only report Shift-Enter with keyboard enhancements enabled
+                KeyCode::Enter if modifiers.contains(KeyModifiers::SHIFT) => {
+                    Ok(Self::SpecialKey(SpecialKey::ShiftEnter))
+                }
                 KeyCode::Enter => Ok(Self::SpecialKey(SpecialKey::Enter)),
+                KeyCode::Esc => Ok(Self::SpecialKey(SpecialKey::Escape)),
                 KeyCode::Tab => Ok(Self::OrdinaryChar(KeyCode::Char('\t'))),
                 KeyCode::BackTab => Ok(Self::SpecialKey(SpecialKey::BackTab)),
                 KeyCode::CapsLock => Ok(Self::SpecialKey(SpecialKey::CapsLock)),
                 KeyCode::Insert => Ok(Self::SpecialKey(SpecialKey::Insert)),
+                // Unbound shortcuts must not insert their letter
+                KeyCode::Char(_)
+                    if modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+                {
+                    Ok(Self::Unknown)
+                }
                 KeyCode::Char(c) => Ok(Self::OrdinaryChar(KeyCode::Char(c))),
                 _ => Ok(Self::Unknown),
             },
+            // Terminals commonly send pasted line breaks as "\r"
+            CrossTermEvent::Paste(text) => Ok(Self::PasteText(
+                text.replace("\r\n", "\n").replace('\r', "\n"),
+            )),
+            CrossTermEvent::Mouse(MouseEvent {
+                kind,
+                column,
+                row,
+                modifiers,
+            }) => {
+                let position = Position {
+                    x: column as usize,
+                    y: row as usize,
+                };
+                match kind {
+                    MouseEventKind::Down(MouseButton::Left)
+                        if modifiers.contains(KeyModifiers::SHIFT) =>
+                    {
+                        Ok(Self::Drag(position))
+                    }
+                    MouseEventKind::Down(MouseButton::Left) => Ok(Self::Click(position)),
+                    MouseEventKind::Drag(MouseButton::Left) => Ok(Self::Drag(position)),
+                    MouseEventKind::ScrollUp => Ok(Self::ScrollUp),
+                    MouseEventKind::ScrollDown => Ok(Self::ScrollDown),
+                    // Other buttons and plain movement are not bound to anything
+                    _ => Ok(Self::Unknown),
+                }
+            }
             CrossTermEvent::Resize(width_u16, height_u16) => {
                 let height = height_u16 as usize;
                 let width = width_u16 as usize;